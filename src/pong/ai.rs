@@ -0,0 +1,308 @@
+// BOT AI
+extern mod std;
+use std::rand::{Rng,XorShiftRng};
+use components::Components;
+use input::PADDLE_SPEED;
+use resources::BallPhysics;
+
+// what a bot knows about the ball, possibly a few ticks out of date
+#[deriving(Clone)]
+pub struct BallView {
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64
+}
+
+impl BallView {
+    pub fn of(ball: &Components) -> BallView {
+        let pos = ball.position.unwrap();
+        let vel = ball.velocity.unwrap();
+        BallView { x: pos.x, y: pos.y, vx: vel.x, vy: vel.y }
+    }
+}
+
+// the part of the arena the ball's center can move in, ie. inside the edges
+// shrunk by the ball's radius
+pub struct Court {
+    min_y: f64,
+    max_y: f64,
+    ball_radius: f64
+}
+
+impl Court {
+    pub fn for_ball(ball: &Components) -> Court {
+        let radius = match ball.collider {
+            Some(ref col) => { let (_, hh) = col.half_extents(); hh },
+            None => 0.0
+        };
+        Court { min_y: radius, max_y: 3.0 - radius, ball_radius: radius }
+    }
+
+    // folds a height the ball would reach without edges back into the court,
+    // the way EdgeCollisionSystem reflects it off the top and bottom
+    pub fn reflect(&self, y: f64) -> f64 {
+        let height = self.max_y - self.min_y;
+        if height <= 0.0 {
+            return self.min_y;
+        }
+        let period = 2.0*height;
+        let mut t = (y - self.min_y) % period;
+        if t < 0.0 {
+            t += period;
+        }
+        if t > height {
+            t = period - t;
+        }
+        self.min_y + t
+    }
+}
+
+pub trait BotStrategy {
+    // height the paddle should move to, `hit_x` being where the ball's center
+    // is when it touches the paddle's face. `entities` are the world's, for
+    // strategies that look at more than the ball.
+    fn target(&mut self, ball: &BallView, hit_x: f64, court: &Court,
+              entities: &[Components], physics: &BallPhysics) -> f64;
+}
+
+// follows the ball's current height
+pub struct Chaser;
+
+impl BotStrategy for Chaser {
+    fn target(&mut self, ball: &BallView, _: f64, _: &Court, _: &[Components], _: &BallPhysics) -> f64 {
+        ball.y
+    }
+}
+
+// works out where the ball will cross the paddle's line, bounces included,
+// and waits in the middle while the ball is heading away
+pub struct Predictor;
+
+impl BotStrategy for Predictor {
+    fn target(&mut self, ball: &BallView, hit_x: f64, court: &Court, _: &[Components], _: &BallPhysics) -> f64 {
+        match intercept(ball, hit_x, court) {
+            Some(y) => y,
+            None => 1.5
+        }
+    }
+}
+
+// height at which the ball reaches `hit_x`, None if it's moving away
+pub fn intercept(ball: &BallView, hit_x: f64, court: &Court) -> Option<f64> {
+    if ball.vx == 0.0 {
+        return None;
+    }
+    let t = (hit_x - ball.x)/ball.vx;
+    if t < 0.0 {
+        None
+    } else {
+        Some(court.reflect(ball.y + ball.vy*t))
+    }
+}
+
+// hit offsets the aimer picks from, as fractions of the paddle's half height
+static AIM_OFFSETS: [f64, ..9] = [-0.8, -0.6, -0.4, -0.2, 0.0, 0.2, 0.4, 0.6, 0.8];
+
+// predicts the intercept like Predictor, then places the paddle so that the
+// ball hits it at the offset whose return lands furthest out of the
+// opponent's reach, as worked out from BallPhysics::paddle_bounce
+pub struct Aimer {
+    // entity ids
+    paddle: uint,
+    opponent: uint,
+    // paddle height to aim for during the current approach, if decided
+    decision: Option<f64>,
+    // prints every decision
+    log: bool
+}
+
+impl Aimer {
+    pub fn new(paddle: uint, opponent: uint, log: bool) -> Aimer {
+        Aimer { paddle: paddle, opponent: opponent, decision: None, log: log }
+    }
+
+    // how far the return from hitting at `offset` lands outside the area the
+    // opponent can cover before it arrives, negative if they can reach it
+    fn score(&self, ball: &BallView, hit_x: f64, hit_y: f64, offset: f64, court: &Court,
+             opponent: &Components, physics: &BallPhysics) -> f64 {
+        let opp_pos = opponent.position.unwrap();
+        let (opp_hw, opp_hh) = opponent.collider.unwrap().half_extents();
+        let normal_x = if opp_pos.x > hit_x { 1.0 } else { -1.0 };
+        let (vx, vy) = physics.paddle_bounce((ball.vx, ball.vy), normal_x, offset, 0.0);
+        let ret = BallView { x: hit_x, y: hit_y, vx: vx, vy: vy };
+        let opp_hit_x = opp_pos.x - normal_x*(opp_hw + court.ball_radius);
+        match intercept(&ret, opp_hit_x, court) {
+            Some(arrival) => {
+                let ticks = (opp_hit_x - hit_x)/vx;
+                let reach = opp_hh + court.ball_radius + ticks*PADDLE_SPEED;
+                std::num::abs(arrival - opp_pos.y) - reach
+            },
+            None => -std::f64::INFINITY
+        }
+    }
+}
+
+impl BotStrategy for Aimer {
+    fn target(&mut self, ball: &BallView, hit_x: f64, court: &Court,
+              entities: &[Components], physics: &BallPhysics) -> f64 {
+        let hit_y = match intercept(ball, hit_x, court) {
+            Some(y) => y,
+            None => {
+                self.decision = None;
+                return 1.5;
+            }
+        };
+        match self.decision {
+            Some(y) => return y,
+            None => ()
+        }
+        let mut best = (0.0, -std::f64::INFINITY);
+        for &offset in AIM_OFFSETS.iter() {
+            let score = self.score(ball, hit_x, hit_y, offset, court, &entities[self.opponent], physics);
+            let (_, best_score) = best;
+            if score > best_score {
+                best = (offset, score);
+            }
+        }
+        let (offset, score) = best;
+        let (_, half_height) = entities[self.paddle].collider.unwrap().half_extents();
+        let target = hit_y - offset*half_height;
+        if self.log {
+            println!("bot: ball arrives at y {:.2f}, hitting at offset {:.1f} (paddle y {:.2f}), \
+                      return lands {:.2f} beyond the opponent's reach",
+                     hit_y, offset, target, score);
+        }
+        self.decision = Some(target);
+        target
+    }
+}
+
+#[deriving(Eq, Clone)]
+pub enum BotAi {
+    ChaserAi,
+    PredictorAi,
+    AimerAi
+}
+
+pub static BOT_AIS: [BotAi, ..3] = [ChaserAi, PredictorAi, AimerAi];
+
+impl BotAi {
+    // `paddle` is the entity id of the bot's own paddle and `opponent` of the
+    // one on the other side, `log` prints the decisions of bots that make any
+    pub fn strategy(&self, paddle: uint, opponent: uint, log: bool) -> ~BotStrategy {
+        match *self {
+            ChaserAi => ~Chaser as ~BotStrategy,
+            PredictorAi => ~Predictor as ~BotStrategy,
+            AimerAi => ~Aimer::new(paddle, opponent, log) as ~BotStrategy
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BotAi> {
+        match name {
+            "chaser" => Some(ChaserAi),
+            "predictor" => Some(PredictorAi),
+            "aimer" => Some(AimerAi),
+            _ => None
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match *self {
+            ChaserAi => "CHASER",
+            PredictorAi => "PREDICTOR",
+            AimerAi => "AIMER"
+        }
+    }
+}
+
+pub struct BotDifficulty {
+    // the bot sees the ball as it was this many ticks ago
+    reaction_ticks: uint,
+    // the aim is off by up to this much, picked anew for every return
+    prediction_error: f64,
+    // in arena units per tick
+    max_speed: f64,
+    // chance of aiming past the paddle on purpose, per return
+    miss_chance: f64,
+    // the paddle stays put when it's closer than this to its target
+    deadzone: f64
+}
+
+#[deriving(Eq, Clone)]
+pub enum BotLevel {
+    // the original bot's, sees the ball straight away and never misses but
+    // only moves when the ball is well away from the paddle's center
+    Classic,
+    Easy,
+    Normal,
+    Hard
+}
+
+pub static BOT_LEVELS: [BotLevel, ..4] = [Classic, Easy, Normal, Hard];
+
+impl BotLevel {
+    pub fn difficulty(&self) -> BotDifficulty {
+        match *self {
+            Classic => BotDifficulty { reaction_ticks: 0, prediction_error: 0.0, max_speed: PADDLE_SPEED, miss_chance: 0.0, deadzone: 0.2 },
+            Easy => BotDifficulty { reaction_ticks: 20, prediction_error: 0.3, max_speed: 1.0/60.0, miss_chance: 0.2, deadzone: 0.02 },
+            Normal => BotDifficulty { reaction_ticks: 10, prediction_error: 0.15, max_speed: 1.5/60.0, miss_chance: 0.08, deadzone: 0.02 },
+            Hard => BotDifficulty { reaction_ticks: 4, prediction_error: 0.05, max_speed: 2.0/60.0, miss_chance: 0.02, deadzone: 0.02 }
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BotLevel> {
+        match name {
+            "classic" => Some(Classic),
+            "easy" => Some(Easy),
+            "normal" => Some(Normal),
+            "hard" => Some(Hard),
+            _ => None
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Classic => "CLASSIC",
+            Easy => "EASY",
+            Normal => "NORMAL",
+            Hard => "HARD"
+        }
+    }
+}
+
+// how far off a bot aims on this return, `reach` being the distance from the
+// paddle's center at which it still hits the ball
+pub fn aim_error(difficulty: &BotDifficulty, reach: f64, rng: &mut XorShiftRng) -> f64 {
+    let mut error = if difficulty.prediction_error > 0.0 {
+        rng.gen_range(-difficulty.prediction_error, difficulty.prediction_error)
+    } else {
+        0.0
+    };
+    if rng.gen::<f64>() < difficulty.miss_chance {
+        error += if rng.gen() { reach + 0.1 } else { -(reach + 0.1) };
+    }
+    error
+}
+
+#[cfg(test)]
+mod test {
+    use ai::*;
+
+    #[test]
+    fn reflect_folds_into_the_court() {
+        let court = Court { min_y: 0.5, max_y: 2.5, ball_radius: 0.5 };
+        assert_eq!(court.reflect(1.0), 1.0);
+        // off the top and bottom edges
+        assert_eq!(court.reflect(3.0), 2.0);
+        assert_eq!(court.reflect(-0.5), 1.5);
+        // both edges, a whole period later
+        assert_eq!(court.reflect(5.0), 1.0);
+    }
+
+    #[test]
+    fn reflect_in_a_court_with_no_room() {
+        let court = Court { min_y: 1.5, max_y: 1.5, ball_radius: 1.5 };
+        assert_eq!(court.reflect(0.2), 1.5);
+    }
+}
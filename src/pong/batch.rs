@@ -0,0 +1,145 @@
+// BATCHED TRAINING ENVIRONMENTS
+// steps many independent matches at once across worker tasks. The
+// environments are built up front and moved into the workers, after that
+// only seeds, actions and results are sent between tasks.
+extern mod std;
+use std::vec;
+use env::{EnvConfig,PongEnv,STATE_SIZE};
+
+// values per environment in the buffers returned by BatchEnv, the
+// observation state followed by the reward and 1.0 if the episode ended
+pub static BATCH_ROW_SIZE: uint = STATE_SIZE + 2;
+
+enum BatchRequest {
+    // seed of the worker's first environment
+    ResetAll(u32),
+    // one action per environment of the worker
+    StepAll(~[f64]),
+    Shutdown
+}
+
+struct BatchWorker {
+    requests: Chan<BatchRequest>,
+    replies: Port<~[f64]>,
+    envs: uint
+}
+
+// environments whose episode ends are reset straight away with a new seed,
+// so the state returned with `done` belongs to the next episode
+pub struct BatchEnv {
+    workers: ~[BatchWorker],
+    num_envs: uint
+}
+
+impl BatchEnv {
+    pub fn new(config: EnvConfig, num_envs: uint, threads: uint) -> BatchEnv {
+        BatchEnv::from_envs(vec::from_fn(num_envs, |_| PongEnv::new(config)), threads)
+    }
+
+    // shares `envs` out between the workers, frames aren't batched so their
+    // `frame_size` is ignored
+    pub fn from_envs(envs: ~[PongEnv], threads: uint) -> BatchEnv {
+        let num_envs = envs.len();
+        let threads = threads.max(&1).min(&num_envs.max(&1));
+        let mut envs = envs;
+        let mut workers = ~[];
+        for t in range(0, threads) {
+            let count = num_envs/threads + if t < num_envs % threads { 1 } else { 0 };
+            let mut share = vec::with_capacity(count);
+            for _ in range(0, count) {
+                let mut env = envs.shift();
+                env.config.frame_size = None;
+                share.push(env);
+            }
+            let (request_port, request_chan): (Port<BatchRequest>, Chan<BatchRequest>) = std::comm::Chan::new();
+            let (reply_port, reply_chan): (Port<~[f64]>, Chan<~[f64]>) = std::comm::Chan::new();
+            do std::task::spawn {
+                run_worker(share, num_envs, request_port, reply_chan);
+            }
+            workers.push(BatchWorker { requests: request_chan, replies: reply_port, envs: count });
+        }
+        BatchEnv { workers: workers, num_envs: num_envs }
+    }
+
+    pub fn num_envs(&self) -> uint {
+        self.num_envs
+    }
+
+    // environment i is seeded with seed + i, returns STATE_SIZE values per
+    // environment
+    pub fn reset(&mut self, seed: u32) -> ~[f64] {
+        let mut first = 0;
+        for worker in self.workers.iter() {
+            worker.requests.send(ResetAll(seed + first as u32));
+            first += worker.envs;
+        }
+        self.gather(self.num_envs*STATE_SIZE)
+    }
+
+    // takes one action per environment, see PongEnv::step, and returns
+    // BATCH_ROW_SIZE values per environment
+    pub fn step(&mut self, actions: &[f64]) -> ~[f64] {
+        if actions.len() != self.num_envs {
+            fail!("BatchEnv::step got {} actions for {} environments", actions.len(), self.num_envs);
+        }
+        let mut first = 0;
+        for worker in self.workers.iter() {
+            worker.requests.send(StepAll(actions.slice(first, first + worker.envs).to_owned()));
+            first += worker.envs;
+        }
+        self.gather(self.num_envs*BATCH_ROW_SIZE)
+    }
+
+    fn gather(&self, size: uint) -> ~[f64] {
+        let mut buffer = vec::with_capacity(size);
+        for worker in self.workers.iter() {
+            buffer.push_all_move(worker.replies.recv());
+        }
+        buffer
+    }
+}
+
+impl Drop for BatchEnv {
+    fn drop(&mut self) {
+        for worker in self.workers.iter() {
+            worker.requests.send(Shutdown);
+        }
+    }
+}
+
+// `stride` is added to an environment's seed every time it's reset after an
+// episode, so that no two episodes of the batch share a seed
+fn run_worker(envs: ~[PongEnv], stride: uint, requests: Port<BatchRequest>, replies: Chan<~[f64]>) {
+    let mut envs = envs;
+    let count = envs.len();
+    let mut seeds = vec::from_elem(count, 0u32);
+    loop {
+        match requests.recv() {
+            ResetAll(first) => {
+                let mut out = vec::with_capacity(count*STATE_SIZE);
+                for (i, env) in envs.mut_iter().enumerate() {
+                    seeds[i] = first + i as u32;
+                    out.push_all_move(env.reset(seeds[i]).state);
+                }
+                replies.send(out);
+            },
+            StepAll(actions) => {
+                let mut out = vec::with_capacity(count*BATCH_ROW_SIZE);
+                for (i, env) in envs.mut_iter().enumerate() {
+                    let (obs, reward, done) = env.step(actions[i]);
+                    let state = if done {
+                        seeds[i] += stride as u32;
+                        env.reset(seeds[i]).state
+                    } else {
+                        obs.state
+                    };
+                    out.push_all_move(state);
+                    out.push(reward);
+                    out.push(if done { 1.0 } else { 0.0 });
+                }
+                replies.send(out);
+            },
+            Shutdown => break
+        }
+    }
+}
@@ -0,0 +1,276 @@
+// KEY BINDINGS
+// which glfw keys trigger which actions, and reading the window and gamepads
+// into an input::InputSnapshot
+extern mod glfw;
+extern mod std;
+
+use std::io::fs::File;
+use std::path::Path;
+use gamepad::Gamepads;
+use input::*;
+
+pub static BINDINGS_FILE: &'static str = "bindings.cfg";
+
+// every key glfw knows, so that any key can be bound and saved
+static KEY_NAMES: [(glfw::Key, &'static str), ..120] = [
+    (glfw::KeyA, "A"), (glfw::KeyB, "B"), (glfw::KeyC, "C"), (glfw::KeyD, "D"), (glfw::KeyE, "E"),
+    (glfw::KeyF, "F"), (glfw::KeyG, "G"), (glfw::KeyH, "H"), (glfw::KeyI, "I"), (glfw::KeyJ, "J"),
+    (glfw::KeyK, "K"), (glfw::KeyL, "L"), (glfw::KeyM, "M"), (glfw::KeyN, "N"), (glfw::KeyO, "O"),
+    (glfw::KeyP, "P"), (glfw::KeyQ, "Q"), (glfw::KeyR, "R"), (glfw::KeyS, "S"), (glfw::KeyT, "T"),
+    (glfw::KeyU, "U"), (glfw::KeyV, "V"), (glfw::KeyW, "W"), (glfw::KeyX, "X"), (glfw::KeyY, "Y"),
+    (glfw::KeyZ, "Z"),
+    (glfw::Key0, "0"), (glfw::Key1, "1"), (glfw::Key2, "2"), (glfw::Key3, "3"), (glfw::Key4, "4"),
+    (glfw::Key5, "5"), (glfw::Key6, "6"), (glfw::Key7, "7"), (glfw::Key8, "8"), (glfw::Key9, "9"),
+    (glfw::KeyUp, "UP"), (glfw::KeyDown, "DOWN"), (glfw::KeyLeft, "LEFT"),
+    (glfw::KeyRight, "RIGHT"), (glfw::KeySpace, "SPACE"), (glfw::KeyEnter, "ENTER"),
+    (glfw::KeyEscape, "ESCAPE"), (glfw::KeyTab, "TAB"), (glfw::KeyBackspace, "BACKSPACE"),
+    (glfw::KeyInsert, "INSERT"), (glfw::KeyDelete, "DELETE"), (glfw::KeyHome, "HOME"),
+    (glfw::KeyEnd, "END"), (glfw::KeyPageUp, "PAGEUP"), (glfw::KeyPageDown, "PAGEDOWN"),
+    (glfw::KeyCapsLock, "CAPSLOCK"), (glfw::KeyScrollLock, "SCROLLLOCK"),
+    (glfw::KeyNumLock, "NUMLOCK"), (glfw::KeyPrintScreen, "PRINTSCREEN"), (glfw::KeyPause, "PAUSE"),
+    (glfw::KeyMenu, "MENU"),
+    (glfw::KeyLeftShift, "LSHIFT"), (glfw::KeyRightShift, "RSHIFT"),
+    (glfw::KeyLeftControl, "LCTRL"), (glfw::KeyRightControl, "RCTRL"), (glfw::KeyLeftAlt, "LALT"),
+    (glfw::KeyRightAlt, "RALT"), (glfw::KeyLeftSuper, "LSUPER"), (glfw::KeyRightSuper, "RSUPER"),
+    // spelled out, the file separates keys with commas
+    (glfw::KeyComma, "COMMA"), (glfw::KeyPeriod, "PERIOD"), (glfw::KeySlash, "SLASH"),
+    (glfw::KeySemicolon, "SEMICOLON"), (glfw::KeyApostrophe, "APOSTROPHE"),
+    (glfw::KeyMinus, "MINUS"), (glfw::KeyEqual, "EQUAL"), (glfw::KeyLeftBracket, "LBRACKET"),
+    (glfw::KeyRightBracket, "RBRACKET"), (glfw::KeyBackslash, "BACKSLASH"),
+    (glfw::KeyGraveAccent, "GRAVE"), (glfw::KeyWorld1, "WORLD1"), (glfw::KeyWorld2, "WORLD2"),
+    (glfw::KeyF1, "F1"), (glfw::KeyF2, "F2"), (glfw::KeyF3, "F3"), (glfw::KeyF4, "F4"),
+    (glfw::KeyF5, "F5"), (glfw::KeyF6, "F6"), (glfw::KeyF7, "F7"), (glfw::KeyF8, "F8"),
+    (glfw::KeyF9, "F9"), (glfw::KeyF10, "F10"), (glfw::KeyF11, "F11"), (glfw::KeyF12, "F12"),
+    (glfw::KeyF13, "F13"), (glfw::KeyF14, "F14"), (glfw::KeyF15, "F15"), (glfw::KeyF16, "F16"),
+    (glfw::KeyF17, "F17"), (glfw::KeyF18, "F18"), (glfw::KeyF19, "F19"), (glfw::KeyF20, "F20"),
+    (glfw::KeyF21, "F21"), (glfw::KeyF22, "F22"), (glfw::KeyF23, "F23"), (glfw::KeyF24, "F24"),
+    (glfw::KeyF25, "F25"),
+    (glfw::KeyKp0, "KP0"), (glfw::KeyKp1, "KP1"), (glfw::KeyKp2, "KP2"), (glfw::KeyKp3, "KP3"),
+    (glfw::KeyKp4, "KP4"), (glfw::KeyKp5, "KP5"), (glfw::KeyKp6, "KP6"), (glfw::KeyKp7, "KP7"),
+    (glfw::KeyKp8, "KP8"), (glfw::KeyKp9, "KP9"), (glfw::KeyKpDecimal, "KPDECIMAL"),
+    (glfw::KeyKpDivide, "KPDIVIDE"), (glfw::KeyKpMultiply, "KPMULTIPLY"),
+    (glfw::KeyKpSubtract, "KPSUBTRACT"), (glfw::KeyKpAdd, "KPADD"), (glfw::KeyKpEnter, "KPENTER"),
+    (glfw::KeyKpEqual, "KPEQUAL")
+];
+
+pub fn key_name(key: glfw::Key) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|&&(k, _)| k == key).map(|&(_, name)| name)
+}
+
+pub fn key_from_name(name: &str) -> Option<glfw::Key> {
+    KEY_NAMES.iter().find(|&&(_, n)| n == name).map(|&(k, _)| k)
+}
+
+// maps keys to actions per player, an action can have any number of keys
+pub struct Bindings {
+    bindings: ~[(uint, Action, ~[glfw::Key])]
+}
+
+impl Bindings {
+    pub fn new() -> Bindings {
+        let mut bindings = ~[];
+        for player in range(0, PLAYERS) {
+            for action in ACTIONS.iter() {
+                if player == 0 || action.is_per_player() {
+                    bindings.push((player, *action, ~[]));
+                }
+            }
+        }
+        Bindings { bindings: bindings }
+    }
+
+    pub fn default() -> Bindings {
+        let mut b = Bindings::new();
+        b.bind(0, PaddleUp, glfw::KeyA);
+        b.bind(0, PaddleUp, glfw::KeyW);
+        b.bind(0, PaddleDown, glfw::KeyZ);
+        b.bind(0, PaddleDown, glfw::KeyS);
+        b.bind(0, Serve, glfw::KeySpace);
+        b.bind(0, Pause, glfw::KeyEscape);
+        b.bind(0, Pause, glfw::KeyP);
+        b.bind(0, Quit, glfw::KeyQ);
+        b.bind(1, PaddleUp, glfw::KeyUp);
+        b.bind(1, PaddleUp, glfw::KeyKp8);
+        b.bind(1, PaddleDown, glfw::KeyDown);
+        b.bind(1, PaddleDown, glfw::KeyKp2);
+        b.bind(1, Serve, glfw::KeyEnter);
+        b.bind(1, Serve, glfw::KeyKpEnter);
+        b
+    }
+
+    pub fn keys<'a>(&'a self, player: uint, action: Action) -> &'a [glfw::Key] {
+        for &(p, a, ref keys) in self.bindings.iter() {
+            if p == player && a == action {
+                return keys.as_slice();
+            }
+        }
+        &[]
+    }
+
+    // whether the key is bound to the action for any player
+    pub fn is_bound(&self, key: glfw::Key, action: Action) -> bool {
+        self.bindings.iter().any(|&(_, a, ref keys)| a == action && keys.contains(&key))
+    }
+
+    pub fn bind(&mut self, player: uint, action: Action, key: glfw::Key) {
+        for &(p, a, ref mut keys) in self.bindings.mut_iter() {
+            if p == player && a == action && !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+
+    pub fn clear(&mut self, player: uint, action: Action) {
+        for &(p, a, ref mut keys) in self.bindings.mut_iter() {
+            if p == player && a == action {
+                keys.clear();
+            }
+        }
+    }
+
+    pub fn describe(&self, player: uint, action: Action) -> ~str {
+        let names: ~[&str] = self.keys(player, action).iter().filter_map(|&k| key_name(k)).collect();
+        if names.is_empty() {
+            ~"-"
+        } else {
+            names.connect(", ")
+        }
+    }
+
+    // the file has one action per line, eg. `paddle_up = A, W`, actions of the
+    // second player are prefixed with `2.`, eg. `2.paddle_up = UP`
+    // unknown actions and keys are skipped with a warning, actions the file
+    // doesn't mention keep their default keys
+    pub fn parse(src: &str) -> Bindings {
+        let mut b = Bindings::default();
+        // the actions seen so far, whose defaults have been replaced
+        let mut seen: ~[(uint, Action)] = ~[];
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let parts: ~[&str] = line.splitn('=', 1).collect();
+            if parts.len() != 2 {
+                println!("bindings: can't parse line `{:s}`", line);
+                continue;
+            }
+            let name = parts[0].trim();
+            let (player, name) = if name.starts_with("2.") {
+                (1u, name.slice_from(2))
+            } else {
+                (0u, name)
+            };
+            let action = match Action::from_name(name) {
+                Some(a) if player == 0 || a.is_per_player() => a,
+                _ => {
+                    println!("bindings: unknown action `{:s}`", parts[0].trim());
+                    continue;
+                }
+            };
+            if !seen.contains(&(player, action)) {
+                b.clear(player, action);
+                seen.push((player, action));
+            }
+            // an action with no keys is written as `serve =`
+            for key in parts[1].split(',').map(|k| k.trim()).filter(|k| !k.is_empty()) {
+                match key_from_name(key) {
+                    Some(k) => b.bind(player, action, k),
+                    None => println!("bindings: unknown key `{:s}`", key)
+                }
+            }
+        }
+        b
+    }
+
+    pub fn to_str(&self) -> ~str {
+        let mut s = ~"";
+        for &(p, a, ref keys) in self.bindings.iter() {
+            let names: ~[&str] = keys.iter().filter_map(|&k| key_name(k)).collect();
+            let prefix = if p == 0 { "" } else { "2." };
+            s.push_str(format!("{:s}{:s} = {:s}\n", prefix, a.name(), names.connect(", ")));
+        }
+        s
+    }
+
+    // falls back to the default bindings if the file doesn't exist
+    pub fn load(path: &str) -> Bindings {
+        let p = Path::new(path);
+        if !p.exists() {
+            return Bindings::default();
+        }
+        match File::open(&p) {
+            Some(mut f) => Bindings::parse(std::str::from_utf8(f.read_to_end())),
+            None => Bindings::default()
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        match File::create(&Path::new(path)) {
+            Some(mut f) => f.write(self.to_str().as_bytes()),
+            None => println!("bindings: can't write {:s}", path)
+        }
+    }
+}
+
+// the input of one frame, keys are looked up through the bindings and the pads
+// are read as of the last Gamepads::poll
+pub fn capture_input(window: &glfw::Window, bindings: &Bindings, gamepads: &Gamepads) -> InputSnapshot {
+    let mut keys = ~[];
+    for &(player, action, ref bound) in bindings.bindings.iter() {
+        if bound.iter().any(|&k| window.get_key(k) == glfw::Press) {
+            keys.push((player, action));
+        }
+    }
+    let mut buttons = ~[];
+    let mut sticks = [0.0, 0.0];
+    for player in range(0, PLAYERS) {
+        for &action in ACTIONS.iter() {
+            if gamepads.is_held(player, action) {
+                buttons.push((player, action));
+            }
+        }
+        sticks[player] = gamepads.paddle_velocity(player);
+    }
+    let (w, h) = window.get_size();
+    InputSnapshot {
+        keys: keys,
+        buttons: buttons,
+        sticks: sticks,
+        cursor: window.get_cursor_pos(),
+        window_size: (w as f64, h as f64),
+        mouse_down: window.get_mouse_button(glfw::MouseButtonLeft) == glfw::Press
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern mod glfw;
+    use bindings::*;
+    use input::*;
+
+    #[test]
+    fn defaults_survive_the_file() {
+        let b = Bindings::default();
+        assert_eq!(Bindings::parse(b.to_str()).to_str(), b.to_str());
+    }
+
+    #[test]
+    fn comma_survives_the_file() {
+        let mut b = Bindings::new();
+        b.bind(0, PaddleUp, glfw::KeyComma);
+        b.bind(0, PaddleUp, glfw::KeyA);
+        let parsed = Bindings::parse(b.to_str());
+        assert_eq!(parsed.keys(0, PaddleUp), &[glfw::KeyComma, glfw::KeyA]);
+    }
+
+    #[test]
+    fn unmentioned_actions_keep_their_defaults() {
+        let parsed = Bindings::parse("paddle_up = UP\n2.serve =\n");
+        let defaults = Bindings::default();
+        assert_eq!(parsed.keys(0, PaddleUp), &[glfw::KeyUp]);
+        assert_eq!(parsed.keys(1, Serve), &[]);
+        assert_eq!(parsed.keys(0, PaddleDown), defaults.keys(0, PaddleDown));
+        assert_eq!(parsed.keys(1, PaddleUp), defaults.keys(1, PaddleUp));
+    }
+}
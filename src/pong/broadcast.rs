@@ -0,0 +1,475 @@
+// MATCH BROADCASTS
+// a read-only stream of a running match for viewers elsewhere and for
+// recording. Every tick is one line of text:
+//   KEY <tick> <setup> <values>
+//   DELTA <tick> <values>
+// where setup is as in MatchSetup::to_words and values are Snapshot values
+// as encoded by server::encode_values, all of them in a keyframe and only
+// those that changed since the tick before in a delta. Keyframes come every
+// few seconds, and a viewer that tunes in late starts with the next one.
+// Recordings hold the same lines, one match after the other, and can be
+// played back with Recording.
+extern mod extra;
+extern mod std;
+use extra::time::precise_time_ns;
+use std::io::buffered::{BufferedReader,BufferedWriter};
+use std::io::fs::File;
+use std::io::io_error;
+use std::io::net::ip::SocketAddr;
+use std::io::net::tcp::{TcpListener,TcpStream};
+use std::io::{Listener,Acceptor,Writer};
+use std::path::Path;
+use components::Components;
+use extbot::read_line;
+use globalsystems::GlobalSystem;
+use input::InputSnapshot;
+use netplay::{MatchSetup,Snapshot};
+use resources::Resources;
+use server::{encode_values,decode_values};
+
+// ticks between keyframes
+pub static KEYFRAME_INTERVAL: uint = 120;
+// ticks between the full states a loaded recording keeps, any tick is
+// rebuilt from the one before it
+pub static SEEK_INTERVAL: uint = 60;
+
+#[deriving(Clone)]
+pub struct BroadcastConfig {
+    // where viewers connect, over TCP
+    address: Option<~str>,
+    // how far behind the match viewers are, in seconds
+    delay_secs: f64,
+    // file to write the stream to
+    record: Option<~str>
+}
+
+impl BroadcastConfig {
+    pub fn new() -> BroadcastConfig {
+        BroadcastConfig { address: None, delay_secs: 0.0, record: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.address.is_some() || self.record.is_some()
+    }
+}
+
+// lines a viewer may fall behind by before it's dropped, a couple of
+// seconds of match
+static VIEWER_QUEUE_LINES: uint = 2*KEYFRAME_INTERVAL;
+
+struct Viewer {
+    lines: Chan<~str>,
+    // acknowledges each line written
+    written: Port<()>,
+    // lines sent to the viewer's task and not written yet
+    queued: uint,
+    // nothing is sent until the next keyframe
+    keyed: bool
+}
+
+// runs in its own task, as accepting connections blocks
+fn accept_viewers(address: ~str, viewers: Chan<TcpStream>) {
+    let addr: SocketAddr = match from_str(address) {
+        Some(addr) => addr,
+        None => {
+            println!("broadcast: bad address {:s}", address);
+            return;
+        }
+    };
+    let mut acceptor = match TcpListener::bind(addr).and_then(|l| l.listen()) {
+        Some(acceptor) => acceptor,
+        None => {
+            println!("broadcast: can't listen on {:s}", address);
+            return;
+        }
+    };
+    println!("broadcast: viewers can connect on {:s}", address);
+    loop {
+        match acceptor.accept() {
+            Some(stream) => if !viewers.try_send(stream) { return },
+            None => ()
+        }
+    }
+}
+
+// runs in a task per viewer, as writing blocks while the viewer is behind,
+// and ends when the viewer is dropped or the connection fails
+fn write_to_viewer(stream: TcpStream, lines: Port<~str>, written: Chan<()>) {
+    let mut stream = stream;
+    loop {
+        let line = match lines.recv_opt() {
+            Some(line) => line,
+            None => return
+        };
+        let mut failed = false;
+        io_error::cond.trap(|_| failed = true).inside(|| {
+            stream.write(line.as_bytes());
+            stream.write(['\n' as u8]);
+        });
+        if failed || !written.try_send(()) {
+            return;
+        }
+    }
+}
+
+// runs in its own task and hands every line to the viewers' tasks. A viewer
+// more than VIEWER_QUEUE_LINES behind is dropped, so that one slow viewer
+// holds up neither the match nor the other viewers.
+fn send_to_viewers(lines: Port<~str>, new_viewers: Port<TcpStream>) {
+    let mut viewers: ~[Viewer] = ~[];
+    loop {
+        let line = match lines.recv_opt() {
+            Some(line) => line,
+            None => return
+        };
+        loop {
+            match new_viewers.try_recv() {
+                Some(stream) => {
+                    let (viewer_lines, viewer_chan): (Port<~str>, Chan<~str>) = std::comm::Chan::new();
+                    let (written_port, written_chan): (Port<()>, Chan<()>) = std::comm::Chan::new();
+                    do std::task::spawn {
+                        write_to_viewer(stream, viewer_lines, written_chan);
+                    }
+                    viewers.push(Viewer { lines: viewer_chan, written: written_port, queued: 0, keyed: false });
+                },
+                None => break
+            }
+        }
+        let is_key = line.starts_with("KEY");
+        let mut gone = ~[];
+        for (i, viewer) in viewers.mut_iter().enumerate() {
+            loop {
+                match viewer.written.try_recv() {
+                    Some(()) => viewer.queued -= 1,
+                    None => break
+                }
+            }
+            viewer.keyed = viewer.keyed || is_key;
+            if !viewer.keyed {
+                continue;
+            }
+            if viewer.queued >= VIEWER_QUEUE_LINES {
+                println!("broadcast: dropping a viewer that fell behind");
+                gone.push(i);
+            } else if viewer.lines.try_send(line.clone()) {
+                viewer.queued += 1;
+            } else {
+                // its task has ended, the connection failed
+                gone.push(i);
+            }
+        }
+        for &i in gone.iter().invert() {
+            viewers.remove(i);
+        }
+    }
+}
+
+// hands the stream to viewers and the recording, shared by every match
+// played while the game runs. Matches send their lines through `sender`,
+// which can go wherever the match goes.
+pub struct Broadcaster {
+    lines: Port<~str>,
+    chan: SharedChan<~str>,
+    viewers: Option<Chan<~str>>,
+    recording: Option<BufferedWriter<File>>,
+    delay_ns: u64,
+    // lines held back by the delay, with the time they're due
+    delayed: ~[(u64, ~str)]
+}
+
+impl Broadcaster {
+    pub fn new(config: &BroadcastConfig) -> Broadcaster {
+        let viewers = match config.address {
+            Some(ref address) => {
+                let address = address.clone();
+                let (line_port, line_chan): (Port<~str>, Chan<~str>) = std::comm::Chan::new();
+                let (viewer_port, viewer_chan): (Port<TcpStream>, Chan<TcpStream>) = std::comm::Chan::new();
+                do std::task::spawn {
+                    accept_viewers(address, viewer_chan);
+                }
+                do std::task::spawn {
+                    send_to_viewers(line_port, viewer_port);
+                }
+                Some(line_chan)
+            },
+            None => None
+        };
+        let recording = match config.record {
+            Some(ref path) => match io_error::cond.trap(|_| ()).inside(|| File::create(&Path::new(path.as_slice()))) {
+                Some(file) => {
+                    println!("broadcast: recording to {:s}", *path);
+                    Some(BufferedWriter::new(file))
+                },
+                None => {
+                    println!("broadcast: can't write {:s}", *path);
+                    None
+                }
+            },
+            None => None
+        };
+        let (lines, chan): (Port<~str>, SharedChan<~str>) = std::comm::SharedChan::new();
+        Broadcaster {
+            lines: lines,
+            chan: chan,
+            viewers: viewers,
+            recording: recording,
+            delay_ns: (config.delay_secs*1e9) as u64,
+            delayed: ~[]
+        }
+    }
+
+    // for a BroadcastSystem to send its lines to
+    pub fn sender(&self) -> SharedChan<~str> {
+        self.chan.clone()
+    }
+
+    // recordings get the line straight away, viewers after the delay
+    fn send(&mut self, line: ~str) {
+        let mut failed = false;
+        match self.recording {
+            Some(ref mut w) => io_error::cond.trap(|_| failed = true).inside(|| {
+                w.write(line.as_bytes());
+                w.write(['\n' as u8]);
+                // so that a recording cut short is still good up to here
+                if line.starts_with("KEY") {
+                    w.flush();
+                }
+            }),
+            None => ()
+        }
+        if failed {
+            println!("broadcast: can't write the recording, recording stopped");
+            // dropping the writer flushes what's left, which fails as well
+            io_error::cond.trap(|_| ()).inside(|| self.recording = None);
+        }
+        if self.viewers.is_some() {
+            self.delayed.push((precise_time_ns() + self.delay_ns, line));
+        }
+    }
+
+    // takes the lines matches have sent and sends viewers those whose delay
+    // has passed, called every frame so that the end of a match goes out
+    // even when nothing follows it
+    pub fn release(&mut self) {
+        loop {
+            match self.lines.try_recv() {
+                Some(line) => self.send(line),
+                None => break
+            }
+        }
+        let now = precise_time_ns();
+        let viewers = match self.viewers {
+            Some(ref viewers) => viewers,
+            None => return
+        };
+        while !self.delayed.is_empty() {
+            match self.delayed[0] {
+                (due, _) if due <= now => (),
+                _ => break
+            }
+            let (_, line) = self.delayed.shift();
+            viewers.try_send(line);
+        }
+    }
+}
+
+// sends each tick of a match to a broadcaster, goes after the systems that
+// change the state
+pub struct BroadcastSystem {
+    lines: SharedChan<~str>,
+    setup: MatchSetup,
+    // how many entities new_match_world made, those after it aren't sent
+    match_entities: uint,
+    tick: uint,
+    last: Option<~[u64]>
+}
+
+impl BroadcastSystem {
+    pub fn new(lines: SharedChan<~str>, setup: MatchSetup, match_entities: uint) -> BroadcastSystem {
+        BroadcastSystem {
+            lines: lines,
+            setup: setup,
+            match_entities: match_entities,
+            tick: 0,
+            last: None
+        }
+    }
+}
+
+impl GlobalSystem for BroadcastSystem {
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, _: &InputSnapshot) -> () {
+        let values = Snapshot::of(entities.slice_to(self.match_entities), resources).values();
+        let line = match self.last {
+            Some(ref last) if self.tick % KEYFRAME_INTERVAL != 0 =>
+                format!("DELTA {} {:s}", self.tick, encode_values(values, Some(last.as_slice()))),
+            _ => format!("KEY {} {:s} {:s}", self.tick, self.setup.to_words(), encode_values(values, None))
+        };
+        self.lines.try_send(line);
+        self.last = Some(values);
+        self.tick += 1;
+    }
+}
+
+// turns stream lines back into states
+pub struct StreamReader {
+    // of the current match, from its keyframes
+    setup: Option<MatchSetup>,
+    tick: uint,
+    values: Option<~[u64]>,
+    // matches seen so far, a new one starts with a keyframe that goes back
+    // in time
+    matches: uint
+}
+
+impl StreamReader {
+    pub fn new() -> StreamReader {
+        StreamReader { setup: None, tick: 0, values: None, matches: 0 }
+    }
+
+    // false for lines that can't be used, such as deltas before the first
+    // keyframe or after a gap
+    pub fn read(&mut self, line: &str) -> bool {
+        let words: ~[&str] = line.words().collect();
+        if words.len() == 10 && words[0] == "KEY" {
+            match (from_str::<uint>(words[1]), MatchSetup::from_words(words.slice(2, 9)), decode_values(words[9], ~[])) {
+                (Some(tick), Some(setup), Some(values)) => {
+                    if self.setup.is_none() || tick <= self.tick {
+                        self.matches += 1;
+                    }
+                    self.setup = Some(setup);
+                    self.tick = tick;
+                    self.values = Some(values);
+                    true
+                },
+                (_, _, _) => false
+            }
+        } else if words.len() == 3 && words[0] == "DELTA" {
+            let tick = match from_str::<uint>(words[1]) {
+                Some(tick) => tick,
+                None => return false
+            };
+            let values = match self.values.take() {
+                Some(values) if tick == self.tick + 1 => decode_values(words[2], values),
+                _ => None
+            };
+            self.tick = tick;
+            self.values = values;
+            self.values.is_some()
+        } else {
+            false
+        }
+    }
+}
+
+// connects to a broadcast and passes its lines on, an empty line when the
+// stream ends
+pub fn watch(address: ~str) -> Port<~str> {
+    let (port, chan): (Port<~str>, Chan<~str>) = std::comm::Chan::new();
+    do std::task::spawn {
+        match from_str::<SocketAddr>(address).and_then(|addr| TcpStream::connect(addr)) {
+            Some(mut stream) => loop {
+                match read_line(&mut stream) {
+                    Some(line) => if !line.is_empty() && !chan.try_send(line) { break },
+                    None => break
+                }
+            },
+            None => println!("broadcast: can't connect to {:s}", address)
+        }
+        chan.try_send(~"");
+    }
+    port
+}
+
+struct RecordedTick {
+    // index into Recording::setups
+    match_index: uint,
+    // (index, value) pairs that changed since the tick before, or all of
+    // them when the tick starts a match
+    changes: ~[(uint, u64)],
+    full: bool
+}
+
+// a recording read back into memory, for playing it forwards and backwards
+pub struct Recording {
+    // one per match
+    setups: ~[MatchSetup],
+    ticks: ~[RecordedTick],
+    // values of every SEEK_INTERVAL-th tick
+    snapshots: ~[~[u64]]
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Option<Recording> {
+        let file = io_error::cond.trap(|_| ()).inside(|| File::open(&Path::new(path)));
+        let file = match file {
+            Some(file) => file,
+            None => {
+                println!("broadcast: can't read {:s}", path);
+                return None;
+            }
+        };
+        let mut file = BufferedReader::new(file);
+        let mut reader = StreamReader::new();
+        let mut recording = Recording { setups: ~[], ticks: ~[], snapshots: ~[] };
+        let mut last: ~[u64] = ~[];
+        loop {
+            let line = match read_line(&mut file) {
+                Some(line) => line,
+                None => break
+            };
+            // lines that can't be used leave a gap, which is skipped over
+            if !reader.read(line) {
+                continue;
+            }
+            let values = reader.values.get_ref().clone();
+            let full = reader.matches != recording.setups.len() || values.len() != last.len();
+            if reader.matches != recording.setups.len() {
+                recording.setups.push(*reader.setup.get_ref());
+            }
+            let changes = if full {
+                values.iter().map(|&v| v).enumerate().collect()
+            } else {
+                values.iter().zip(last.iter()).enumerate()
+                    .filter(|&(_, (v, l))| *v != *l)
+                    .map(|(i, (&v, _))| (i, v)).collect()
+            };
+            if recording.ticks.len() % SEEK_INTERVAL == 0 {
+                recording.snapshots.push(values.clone());
+            }
+            recording.ticks.push(RecordedTick { match_index: recording.setups.len() - 1, changes: changes, full: full });
+            last = values;
+        }
+        if recording.ticks.is_empty() {
+            println!("broadcast: {:s} has no matches in it", path);
+            return None;
+        }
+        println!("broadcast: {:s} has {} matches, {} ticks", path, recording.setups.len(), recording.ticks.len());
+        Some(recording)
+    }
+
+    pub fn len(&self) -> uint {
+        self.ticks.len()
+    }
+
+    // the match `tick` belongs to, as an index into `setups`
+    pub fn match_at(&self, tick: uint) -> uint {
+        self.ticks[tick].match_index
+    }
+
+    // starts from the last full state at or before `tick`, so going back is
+    // as cheap as going forward
+    pub fn values_at(&self, tick: uint) -> ~[u64] {
+        let first = tick - tick % SEEK_INTERVAL;
+        let mut values = self.snapshots[first / SEEK_INTERVAL].clone();
+        for t in self.ticks.slice(first + 1, tick + 1).iter() {
+            if t.full {
+                values = t.changes.iter().map(|&(_, v)| v).collect();
+            } else {
+                for &(i, v) in t.changes.iter() {
+                    values[i] = v;
+                }
+            }
+        }
+        values
+    }
+}
@@ -10,12 +10,20 @@ impl glfw::ErrorCallback for ErrorContext {
     }
 }
 
-pub struct KeyContext;
+// forwards key presses to the game states, along with whether the press is
+// a repeat from the key being held down
+pub struct KeyContext {
+    chan: Chan<(glfw::Key, bool)>
+}
+
 impl glfw::KeyCallback for KeyContext {
-    fn call(&self, window: &glfw::Window, key: glfw::Key, _scancode: libc::c_int, action: glfw::Action, _mods: glfw::Modifiers) {
-        match (key, action) {
-            (glfw::KeyEscape, glfw::Press) => {
-                window.set_should_close(true);
+    fn call(&self, _: &glfw::Window, key: glfw::Key, _scancode: libc::c_int, action: glfw::Action, _mods: glfw::Modifiers) {
+        match action {
+            glfw::Press => {
+                self.chan.send((key, false));
+            }
+            glfw::Repeat => {
+                self.chan.send((key, true));
             }
 
             _ => ()
@@ -32,3 +40,14 @@ impl glfw::FramebufferSizeCallback for FramebufferSizeContext {
         self.chan.send((width as u32,height as u32));
     }
 }
+
+// forwards typed characters, for the console
+pub struct CharContext {
+    chan: Chan<char>
+}
+
+impl glfw::CharCallback for CharContext {
+    fn call(&self, _: &glfw::Window, character: char) {
+        self.chan.send(character);
+    }
+}
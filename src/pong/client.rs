@@ -0,0 +1,241 @@
+// DEDICATED SERVER CLIENT
+// shows the match a server runs, see server.rs. States from the server are
+// already a little old when they arrive, so a player's own paddle is moved
+// on from where the server has it by the inputs the server hasn't applied
+// yet. Everything else is shown as the server last had it.
+extern mod extra;
+extern mod std;
+use extra::time::precise_time_ns;
+use std::io::net::ip::SocketAddr;
+use components::Components;
+use entities::PaddleSide;
+use input::PADDLE_SPEED;
+use netplay::*;
+use server::*;
+
+// how often JOIN is sent until the server answers, in ticks
+static JOIN_INTERVAL: uint = 30;
+// inputs kept for resending, older ones are given up on
+static MAX_UNAPPLIED_INPUTS: uint = 64;
+// a server that hasn't been heard from for this long is gone
+static SERVER_TIMEOUT_NS: u64 = 5000000000;
+
+#[deriving(Clone)]
+pub struct ClientConfig {
+    server: SocketAddr,
+    local_port: u16,
+    conditions: NetConditions,
+    // watch even when a player's place is free
+    spectate: bool
+}
+
+pub struct RemoteMatch {
+    config: ClientConfig,
+    transport: Option<Transport>,
+    // None until the server has accepted
+    role: Option<ClientRole>,
+    // the server turned us away
+    full: bool,
+    view: Option<MatchView>,
+    match_id: uint,
+    // values of the last few states at tick % STATE_HISTORY
+    states: ~[Option<(uint, ~[u64])>],
+    latest: Option<uint>,
+    // inputs the server hasn't applied yet, starting with number `first_seq`
+    unapplied: ~[NetInput],
+    first_seq: uint,
+    ticks: uint,
+    last_heard: u64
+}
+
+impl RemoteMatch {
+    pub fn new(config: ClientConfig) -> RemoteMatch {
+        let transport = Transport::bind(config.local_port, config.conditions.clone());
+        if transport.is_none() {
+            println!("client: can't listen on port {}", config.local_port);
+        }
+        RemoteMatch {
+            config: config,
+            transport: transport,
+            role: None,
+            full: false,
+            view: None,
+            match_id: 0,
+            states: std::vec::from_fn(STATE_HISTORY, |_| None),
+            latest: None,
+            unapplied: ~[],
+            first_seq: 1,
+            ticks: 0,
+            last_heard: precise_time_ns()
+        }
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.transport.is_some()
+    }
+
+    pub fn server_lost(&self) -> bool {
+        self.role.is_some() && precise_time_ns() - self.last_heard > SERVER_TIMEOUT_NS
+    }
+
+    pub fn winner(&self) -> Option<PaddleSide> {
+        match self.view {
+            Some(ref view) => view.game.world.resources.match_state.winner,
+            None => None
+        }
+    }
+
+    // called once a frame with the local player's input, which is ignored
+    // when watching
+    pub fn update(&mut self, local: NetInput) {
+        if self.role.is_none() && !self.full && self.ticks % JOIN_INTERVAL == 0 {
+            let join = format!("JOIN {} {:s}", self.config.local_port, if self.config.spectate { "watch" } else { "play" });
+            self.send(join);
+        }
+        self.ticks += 1;
+        self.receive();
+        let (m, tick) = (self.match_id, self.latest.unwrap_or(0));
+        match self.role {
+            Some(Player(_)) => {
+                self.unapplied.push(local);
+                if self.unapplied.len() > MAX_UNAPPLIED_INPUTS {
+                    self.unapplied.shift();
+                    self.first_seq += 1;
+                }
+                let codes: ~str = self.unapplied.iter().map(|i| i.encode()).collect();
+                let packet = format!("INPUT {} {} {:s} {} {}", self.config.local_port, self.first_seq, codes, m, tick);
+                self.send(packet);
+            },
+            Some(Spectator) => {
+                let packet = format!("ACK {} {} {}", self.config.local_port, m, tick);
+                self.send(packet);
+            },
+            None => ()
+        }
+        self.show_latest();
+        match self.transport {
+            Some(ref mut transport) => transport.flush(),
+            None => ()
+        }
+    }
+
+    fn send(&mut self, line: &str) {
+        match self.transport {
+            Some(ref mut transport) => transport.send(self.config.server, line),
+            None => ()
+        }
+    }
+
+    fn receive(&mut self) {
+        loop {
+            let packet = match self.transport {
+                Some(ref mut transport) => transport.receive(),
+                None => None
+            };
+            let (line, from) = match packet {
+                Some(packet) => packet,
+                None => break
+            };
+            if from.ip != self.config.server.ip {
+                continue;
+            }
+            self.last_heard = precise_time_ns();
+            let words: ~[&str] = line.words().collect();
+            if words.len() == 9 && words[0] == "ACCEPT" && self.role.is_none() {
+                match (ClientRole::from_name(words[1]), MatchSetup::from_words(words.slice_from(2))) {
+                    (Some(role), Some(setup)) => self.accepted(role, &setup),
+                    (_, _) => ()
+                }
+            } else if words.len() == 1 && words[0] == "FULL" && self.role.is_none() {
+                println!("client: the server is full");
+                self.full = true;
+            } else if words.len() == 6 && words[0] == "STATE" {
+                self.state_arrived(words);
+            }
+        }
+    }
+
+    fn accepted(&mut self, role: ClientRole, setup: &MatchSetup) {
+        println!("client: joined as {:s}", role.name());
+        self.view = Some(MatchView::new(setup));
+        self.role = Some(role);
+    }
+
+    fn state_values(&self, tick: uint) -> Option<~[u64]> {
+        match self.states[tick % STATE_HISTORY] {
+            Some((t, ref values)) if t == tick => Some(values.clone()),
+            _ => None
+        }
+    }
+
+    fn state_arrived(&mut self, words: &[&str]) {
+        let (m, tick, seq) = match (from_str::<uint>(words[1]), from_str::<uint>(words[2]), from_str::<uint>(words[4])) {
+            (Some(m), Some(tick), Some(seq)) => (m, tick, seq),
+            (_, _, _) => return
+        };
+        if m != self.match_id {
+            self.match_id = m;
+            self.states = std::vec::from_fn(STATE_HISTORY, |_| None);
+            self.latest = None;
+        }
+        let base = if words[3] == "-" {
+            Some(~[])
+        } else {
+            from_str::<uint>(words[3]).and_then(|b| self.state_values(b))
+        };
+        let values = match base.and_then(|base| decode_values(words[5], base)) {
+            Some(values) => values,
+            None => return
+        };
+        self.states[tick % STATE_HISTORY] = Some((tick, values));
+        match self.latest {
+            Some(latest) if latest >= tick => (),
+            _ => self.latest = Some(tick)
+        }
+        self.inputs_applied(seq);
+    }
+
+    // the server has applied every input up to `seq`
+    fn inputs_applied(&mut self, seq: uint) {
+        while self.first_seq <= seq && !self.unapplied.is_empty() {
+            self.unapplied.shift();
+            self.first_seq += 1;
+        }
+    }
+
+    // puts the newest state into the world
+    fn show_latest(&mut self) {
+        let values = match self.latest.and_then(|tick| self.state_values(tick)) {
+            Some(values) => values,
+            None => return
+        };
+        match self.view {
+            Some(ref mut view) => if view.show(values) {
+                match self.role {
+                    Some(Player(side)) => {
+                        let paddle = view.game.paddle(side);
+                        predict(&mut view.game.world.entities[paddle], self.unapplied);
+                    },
+                    _ => ()
+                }
+            },
+            None => ()
+        }
+    }
+}
+
+// moves the paddle on by the inputs the server hasn't applied yet
+fn predict(paddle: &mut Components, unapplied: &[NetInput]) {
+    match *paddle {
+        Components { position: Some(ref mut pos), bounds: ref bounds, .. } => {
+            for input in unapplied.iter() {
+                pos.y += input.dir as f64*PADDLE_SPEED;
+            }
+            match *bounds {
+                Some(ref bounds) => pos.y = pos.y.max(&bounds.min_y).min(&bounds.max_y),
+                None => ()
+            }
+        },
+        _ => ()
+    }
+}
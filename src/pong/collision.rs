@@ -0,0 +1,279 @@
+// COLLISION DETECTION
+extern mod std;
+use components::*;
+use resources::Resources;
+use systems::System;
+
+// collision layers, a collider is only tested against colliders whose layer
+// is included in its mask
+pub static LAYER_BALL: u32 = 1;
+pub static LAYER_PADDLE: u32 = 2;
+pub static LAYER_WALL: u32 = 4;
+
+pub struct Contact {
+    // fraction of the tick's movement before the shapes touch, in [0, 1]
+    time: f64,
+    // surface normal of the shape that was hit
+    normal: (f64, f64)
+}
+
+pub struct CollisionEvent {
+    // entity ids, ie. indices into World.entities
+    // `a` is the entity whose mask matched the layer of `b`
+    a: uint,
+    b: uint,
+    time: f64,
+    // surface normal of `b` at the contact point, pointing towards `a`
+    normal: (f64, f64)
+}
+
+// collisions found during the current tick, kept in the world's resources
+// for the gameplay systems that respond to them. Sorted by time, with only
+// the earliest event of each entity `a`, as responding to it rewinds the
+// entity and changes its course for the rest of the tick.
+pub struct CollisionEvents {
+    events: ~[CollisionEvent]
+}
+
+impl CollisionEvents {
+    pub fn new() -> CollisionEvents {
+        CollisionEvents { events: ~[] }
+    }
+}
+
+// the entity's movement during the tick is taken to be its velocity, which is
+// exact for SemiImplicitEuler and close enough for the other integrators as
+// long as accelerations stay small
+pub fn velocity(entity: &Components) -> (f64, f64) {
+    match entity.velocity {
+        Some(v) => (v.x, v.y),
+        None => (0.0, 0.0)
+    }
+}
+
+pub fn in_layer(entity: &Components, layer: u32) -> bool {
+    match entity.collider {
+        Some(col) => col.layer & layer != 0,
+        None => false
+    }
+}
+
+// moves an entity back along its velocity to where it was at `time` of the tick
+pub fn rewind(entity: &mut Components, time: f64) {
+    let (vx, vy) = velocity(entity);
+    match entity.position {
+        Some(ref mut pos) => {
+            pos.x -= vx*(1.0 - time);
+            pos.y -= vy*(1.0 - time);
+        },
+        None => ()
+    }
+}
+
+//swept AABB collision detection
+// `origin` is the moving box's center relative to the static box's center and
+// `half_size` the sum of both boxes' half sizes, ie. the static box grown by the
+// moving one so that the test reduces to a ray against a box.
+// Boxes that already overlap at the start of the movement don't collide.
+pub fn sweepAABB(origin: (f64, f64), motion: (f64, f64), half_size: (f64, f64)) -> Option<Contact> {
+    let (ox, oy) = origin;
+    let (dx, dy) = motion;
+    let (hw, hh) = half_size;
+
+    fn slab(o: f64, d: f64, h: f64) -> Option<(f64, f64)> {
+        if d == 0.0 {
+            if std::num::abs(o) < h {
+                Some((-std::f64::INFINITY, std::f64::INFINITY))
+            } else {
+                None
+            }
+        } else {
+            let t1 = (-h - o)/d;
+            let t2 = (h - o)/d;
+            Some((t1.min(&t2), t1.max(&t2)))
+        }
+    }
+
+    match (slab(ox, dx, hw), slab(oy, dy, hh)) {
+        (Some((x_entry, x_exit)), Some((y_entry, y_exit))) => {
+            let entry = x_entry.max(&y_entry);
+            let exit = x_exit.min(&y_exit);
+            if entry > exit || entry < 0.0 || entry > 1.0 {
+                None
+            } else if x_entry > y_entry {
+                Some(Contact { time: entry, normal: (if dx > 0.0 { -1.0 } else { 1.0 }, 0.0) })
+            } else {
+                Some(Contact { time: entry, normal: (0.0, if dy > 0.0 { -1.0 } else { 1.0 }) })
+            }
+        },
+        (_, _) => None
+    }
+}
+
+//swept circle collision detection
+// same conventions as sweepAABB, `radius` being the sum of both radii
+pub fn sweepCircles(origin: (f64, f64), motion: (f64, f64), radius: f64) -> Option<Contact> {
+    let (ox, oy) = origin;
+    let (dx, dy) = motion;
+    let a = dx*dx + dy*dy;
+    let b = 2.0*(ox*dx + oy*dy);
+    let c = ox*ox + oy*oy - radius*radius;
+    if c < 0.0 || a == 0.0 {
+        return None;
+    }
+    let disc = b*b - 4.0*a*c;
+    if disc < 0.0 {
+        return None;
+    }
+    let t = (-b - disc.sqrt())/(2.0*a);
+    if t < 0.0 || t > 1.0 {
+        None
+    } else {
+        Some(Contact { time: t, normal: ((ox + dx*t)/radius, (oy + dy*t)/radius) })
+    }
+}
+
+// sweeps `a` against `b`, both moving by their velocity during the tick
+pub fn sweepEntities(a: &Components, b: &Components) -> Option<Contact> {
+    match (a.position, a.collider, b.position, b.collider) {
+        (Some(a_pos), Some(a_col), Some(b_pos), Some(b_col)) => {
+            let (avx, avy) = velocity(a);
+            let (bvx, bvy) = velocity(b);
+            // MovementSystem has already moved both, so sweep from their previous
+            // positions in the frame of reference of `b`
+            let origin = ((a_pos.x - avx) - (b_pos.x - bvx), (a_pos.y - avy) - (b_pos.y - bvy));
+            let motion = (avx - bvx, avy - bvy);
+            match (a_col.shape, b_col.shape) {
+                (CircleShape(ra), CircleShape(rb)) => sweepCircles(origin, motion, ra + rb),
+                // circles against boxes are swept as boxes, which is slightly
+                // generous at the corners
+                (_, _) => {
+                    let (a_hw, a_hh) = a_col.half_extents();
+                    let (b_hw, b_hh) = b_col.half_extents();
+                    sweepAABB(origin, motion, (a_hw + b_hw, a_hh + b_hh))
+                }
+            }
+        },
+        (_, _, _, _) => None
+    }
+}
+
+// bounding box covering the whole movement of the entity during the tick,
+// as [min_x, max_x, min_y, max_y]
+fn swept_bounds(entity: &Components) -> Option<[f64, ..4]> {
+    match (entity.position, entity.collider) {
+        (Some(pos), Some(col)) => {
+            let (vx, vy) = velocity(entity);
+            let (hw, hh) = col.half_extents();
+            Some([pos.x.min(&(pos.x - vx)) - hw, pos.x.max(&(pos.x - vx)) + hw,
+                  pos.y.min(&(pos.y - vy)) - hh, pos.y.max(&(pos.y - vy)) + hh])
+        },
+        None => None
+    }
+}
+
+// finds the collisions of the tick and puts them into the world's
+// CollisionEvents
+pub struct CollisionSystem;
+
+fn narrow_phase(entities: &[Components], events: &mut CollisionEvents, a: uint, b: uint) {
+    let (a_col, b_col) = (entities[a].collider.unwrap(), entities[b].collider.unwrap());
+    if a_col.mask & b_col.layer == 0 {
+        return
+    }
+    match sweepEntities(&entities[a], &entities[b]) {
+        Some(Contact { time: t, normal: n }) => {
+            events.events.push(CollisionEvent { a: a, b: b, time: t, normal: n });
+        },
+        None => ()
+    }
+}
+
+impl System for CollisionSystem {
+    fn process(&self, _: &mut Components, _: &mut Resources) -> () {
+    }
+
+    fn process_all(&self, entities: &mut [Components], resources: &mut Resources) -> () {
+        let events = &mut resources.collisions;
+        events.events.clear();
+
+        // broad phase: sort and sweep along x axis
+        let mut bounds: ~[(uint, [f64, ..4])] = ~[];
+        for (i, entity) in entities.iter().enumerate() {
+            match swept_bounds(entity) {
+                Some(b) => bounds.push((i, b)),
+                None => ()
+            }
+        }
+        bounds.sort_by(|&(_, a), &(_, b)| {
+            if a[0] < b[0] { Less } else if a[0] > b[0] { Greater } else { Equal }
+        });
+
+        let mut active: ~[(uint, [f64, ..4])] = ~[];
+        for &(i, bi) in bounds.iter() {
+            active.retain(|&(_, bj)| bj[1] >= bi[0]);
+            for &(j, bj) in active.iter() {
+                if bj[2] <= bi[3] && bi[2] <= bj[3] {
+                    narrow_phase(entities, events, i, j);
+                    narrow_phase(entities, events, j, i);
+                }
+            }
+            active.push((i, bi));
+        }
+
+        events.events.sort_by(|a, b| {
+            if a.time < b.time { Less } else if a.time > b.time { Greater } else { Equal }
+        });
+        let mut resolved: ~[uint] = ~[];
+        events.events.retain(|event| {
+            if resolved.contains(&event.a) {
+                false
+            } else {
+                resolved.push(event.a);
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use collision::*;
+
+    #[test]
+    fn boxes_meet_head_on() {
+        let c = sweepAABB((-2.0, 0.0), (2.0, 0.0), (1.0, 1.0)).unwrap();
+        assert_eq!(c.time, 0.5);
+        assert_eq!(c.normal, (-1.0, 0.0));
+        let c = sweepAABB((0.0, 2.0), (0.0, -2.0), (1.0, 1.0)).unwrap();
+        assert_eq!(c.time, 0.5);
+        assert_eq!(c.normal, (0.0, 1.0));
+    }
+
+    #[test]
+    fn boxes_that_dont_meet_this_tick() {
+        // passes above
+        assert!(sweepAABB((-2.0, 3.0), (4.0, 0.0), (1.0, 1.0)).is_none());
+        // stops short
+        assert!(sweepAABB((-2.0, 0.0), (0.5, 0.0), (1.0, 1.0)).is_none());
+        // moves away
+        assert!(sweepAABB((-2.0, 0.0), (-2.0, 0.0), (1.0, 1.0)).is_none());
+        // already overlapping
+        assert!(sweepAABB((0.0, 0.0), (2.0, 0.0), (1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn circles_meet_head_on() {
+        let c = sweepCircles((-3.0, 0.0), (4.0, 0.0), 1.0).unwrap();
+        assert_eq!(c.time, 0.5);
+        assert_eq!(c.normal, (-1.0, 0.0));
+    }
+
+    #[test]
+    fn circles_that_dont_meet_this_tick() {
+        assert!(sweepCircles((-3.0, 2.0), (4.0, 0.0), 1.0).is_none());
+        assert!(sweepCircles((-3.0, 0.0), (1.0, 0.0), 1.0).is_none());
+        assert!(sweepCircles((0.5, 0.0), (4.0, 0.0), 1.0).is_none());
+        assert!(sweepCircles((-3.0, 0.0), (0.0, 0.0), 1.0).is_none());
+    }
+}
@@ -1,21 +1,29 @@
-use gl::types::*;
-
 // COMPONENT DEFINITIONS
 pub struct Position {
     x: f64,
     y: f64
 }
 
-pub struct HorizVelocity {
-    x: f64
+// in arena units per tick
+pub struct Velocity {
+    x: f64,
+    y: f64
 }
 
-pub struct VertVelocity {
+// in arena units per tick per tick, world gravity is added on top of this
+pub struct Acceleration {
+    x: f64,
     y: f64
 }
 
+pub struct Damping {
+    // fraction of velocity lost every tick
+    factor: f64
+}
+
 pub struct SpriteTexture {
-    texture: GLuint,
+    // a GL texture name, 0 is the character atlas
+    texture: u32,
     texcoords: (uint, uint),
     texsize: (uint, uint)
 }
@@ -47,13 +55,241 @@ pub struct Sprite {
     //instead of color+texture we should have something like material
     //which could be eg enum
     color: [f64, ..4],
-    texture: Option<SpriteTexture>
+    texture: Option<SpriteTexture>,
+    visible: bool,
+    // multiplies the size when drawing only, for effects such as squash that
+    // mustn't change what the sprite size is used for
+    scale: (f64, f64)
+}
+
+// keeps the entity's center within [min_y, max_y], unlike edge collisions
+// the entity just stops at the bound instead of bouncing back
+pub struct MovementBounds {
+    min_y: f64,
+    max_y: f64,
+    // distance from a bound at which movement towards it starts slowing
+    // down, 0.0 for a hard stop
+    ease_distance: f64
+}
+
+pub enum ColliderShape {
+    // x_size, y_size
+    AabbShape(f64, f64),
+    // radius
+    CircleShape(f64)
+}
+
+// hitbox of the entity, centered on its position
+pub struct Collider {
+    shape: ColliderShape,
+    // see the LAYER_ constants in collision.rs
+    layer: u32,
+    mask: u32
+}
+
+impl Collider {
+    pub fn half_extents(&self) -> (f64, f64) {
+        match self.shape {
+            AabbShape(w, h) => (w/2.0, h/2.0),
+            CircleShape(r) => (r, r)
+        }
+    }
+}
+
+// steps through a sequence of atlas frames, replacing the texture of the sprite
+pub struct Animation {
+    frames: ~[SpriteTexture],
+    // how many ticks each frame is shown
+    frame_ticks: uint,
+    looping: bool,
+    current: uint,
+    elapsed: uint
+}
+
+impl Animation {
+    pub fn new(frames: ~[SpriteTexture], frame_ticks: uint, looping: bool) -> Animation {
+        Animation {
+            frames: frames,
+            frame_ticks: frame_ticks,
+            looping: looping,
+            current: 0,
+            elapsed: 0
+        }
+    }
+
+    // one frame per character of the string, eg. "321" for a countdown
+    pub fn from_str(s: &str, frame_ticks: uint, looping: bool) -> Animation {
+        Animation::new(s.chars().map(|c| texture_from_char(c)).collect(), frame_ticks, looping)
+    }
+
+    pub fn restart(&mut self) {
+        self.current = 0;
+        self.elapsed = 0;
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.looping && self.current + 1 >= self.frames.len() && self.elapsed >= self.frame_ticks
+    }
+
+    pub fn frame(&self) -> Option<SpriteTexture> {
+        if self.frames.is_empty() {
+            None
+        } else {
+            Some(self.frames[self.current])
+        }
+    }
+}
+
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    // overshoots the target slightly before settling, good for pops
+    BackOut
+}
+
+impl Easing {
+    // maps linear progress t in [0, 1] to eased progress
+    pub fn apply(&self, t: f64) -> f64 {
+        match *self {
+            Linear => t,
+            QuadIn => t*t,
+            QuadOut => t*(2.0-t),
+            QuadInOut => {
+                if t < 0.5 {
+                    2.0*t*t
+                } else {
+                    -1.0 + (4.0 - 2.0*t)*t
+                }
+            }
+            BackOut => {
+                let c1 = 1.70158;
+                let u = t - 1.0;
+                1.0 + (c1 + 1.0)*u*u*u + c1*u*u
+            }
+        }
+    }
+}
+
+// which value of the entity a tween drives
+#[deriving(Eq)]
+pub enum TweenProperty {
+    // [x, y, _, _]
+    TweenPosition,
+    // [x_size, y_size, _, _]
+    TweenSize,
+    // [r, g, b, a]
+    TweenColor,
+    // [x, y, _, _] of Sprite::scale
+    TweenScale
+}
+
+pub struct Tween {
+    property: TweenProperty,
+    from: [f64, ..4],
+    to: [f64, ..4],
+    // in ticks
+    duration: uint,
+    elapsed: uint,
+    easing: Easing
+}
+
+impl Tween {
+    pub fn new(property: TweenProperty, from: [f64, ..4], to: [f64, ..4], duration: uint, easing: Easing) -> Tween {
+        Tween {
+            property: property,
+            from: from,
+            to: to,
+            duration: duration,
+            elapsed: 0,
+            easing: easing
+        }
+    }
+
+    pub fn position(from: (f64, f64), to: (f64, f64), duration: uint, easing: Easing) -> Tween {
+        let ((fx, fy), (tx, ty)) = (from, to);
+        Tween::new(TweenPosition, [fx, fy, 0.0, 0.0], [tx, ty, 0.0, 0.0], duration, easing)
+    }
+
+    pub fn size(from: (f64, f64), to: (f64, f64), duration: uint, easing: Easing) -> Tween {
+        let ((fw, fh), (tw, th)) = (from, to);
+        Tween::new(TweenSize, [fw, fh, 0.0, 0.0], [tw, th, 0.0, 0.0], duration, easing)
+    }
+
+    pub fn scale(from: (f64, f64), to: (f64, f64), duration: uint, easing: Easing) -> Tween {
+        let ((fx, fy), (tx, ty)) = (from, to);
+        Tween::new(TweenScale, [fx, fy, 0.0, 0.0], [tx, ty, 0.0, 0.0], duration, easing)
+    }
+
+    pub fn color(from: [f64, ..4], to: [f64, ..4], duration: uint, easing: Easing) -> Tween {
+        Tween::new(TweenColor, from, to, duration, easing)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn value(&self) -> [f64, ..4] {
+        let t = if self.duration == 0 {
+            1.0
+        } else {
+            (self.elapsed as f64 / self.duration as f64).min(&1.0)
+        };
+        let e = self.easing.apply(t);
+        let mut v = [0.0, ..4];
+        for i in range(0u, 4) {
+            v[i] = self.from[i] + (self.to[i] - self.from[i])*e;
+        }
+        v
+    }
+}
+
+// all tweens currently running on an entity
+pub struct Tweens {
+    active: ~[Tween]
+}
+
+impl Tweens {
+    pub fn new() -> Tweens {
+        Tweens { active: ~[] }
+    }
+
+    // replaces any running tween on the same property
+    pub fn start(&mut self, tween: Tween) {
+        self.active.retain(|t| t.property != tween.property);
+        self.active.push(tween);
+    }
+
+    // the value the property settles to once running tweens are done,
+    // so that restarting a tween mid-way doesn't drift from the rest value
+    pub fn resting(&self, property: TweenProperty, current: [f64, ..4]) -> [f64, ..4] {
+        for t in self.active.iter() {
+            if t.property == property {
+                return t.to;
+            }
+        }
+        current
+    }
+}
+
+// a line of text drawn with the glyph atlas, the sprite gives the size
+// of a single character
+pub struct Text {
+    text: ~str
 }
 
+// an entity, owned by the world it's in
 pub struct Components {
-    position: Option<@mut Position>,
-    horiz_velocity: Option<@mut HorizVelocity>,
-    vert_velocity: Option<@mut VertVelocity>,
-    sprite: Option<@mut Sprite>,
+    position: Option<Position>,
+    velocity: Option<Velocity>,
+    acceleration: Option<Acceleration>,
+    damping: Option<Damping>,
+    sprite: Option<Sprite>,
+    animation: Option<Animation>,
+    tweens: Option<Tweens>,
+    collider: Option<Collider>,
+    bounds: Option<MovementBounds>,
+    text: Option<Text>,
 }
 
@@ -0,0 +1,248 @@
+// DEVELOPER CONSOLE
+// opened with the key left of 1, takes one command per line, eg.
+//   set ball.speed 2
+//   spawn ball
+//   score left 5
+// Besides the few commands registered with the console itself, commands
+// belong to whatever they change and only exist while it does, eg. `score`
+// is taken by the match while one is running, see CommandScope. The same
+// lines can be put in a script, see CONSOLE_SCRIPT and `exec`.
+extern mod glfw;
+extern mod std;
+use std::io::buffered::BufferedReader;
+use std::io::fs::File;
+use std::io::io_error;
+use std::path::Path;
+use extbot::read_line;
+use graphics::RenderSystem;
+
+// run when the game starts
+pub static CONSOLE_SCRIPT: &'static str = "console.cfg";
+// lines of output kept, and how many of them are shown
+static SCROLLBACK: uint = 200;
+static SHOWN_LINES: uint = 14;
+static CHAR_WIDTH: f64 = 0.03;
+
+pub trait Command {
+    // what is printed back, or why the command failed
+    fn run(&self, args: &[&str]) -> Result<~str, ~str>;
+}
+
+// whatever takes the commands the console doesn't know itself, eg. the game
+// states
+pub trait CommandScope {
+    // None if the command isn't known here
+    fn run_command(&mut self, words: &[&str]) -> Option<Result<~str, ~str>>;
+
+    // lines for `help`
+    fn command_help(&self) -> ~[~str];
+}
+
+struct Registered<T> {
+    name: ~str,
+    // shown by `help`
+    usage: ~str,
+    item: T
+}
+
+pub struct Console {
+    open: bool,
+    input: ~str,
+    lines: ~[~str],
+    // entered lines, oldest first, and the one being looked at with up and
+    // down
+    history: ~[~str],
+    history_pos: Option<uint>,
+    commands: ~[Registered<@Command>],
+    // script lines whose command isn't there yet, they're run once it is
+    waiting: ~[~str]
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            open: false,
+            input: ~"",
+            lines: ~[],
+            history: ~[],
+            history_pos: None,
+            commands: ~[],
+            waiting: ~[]
+        }
+    }
+
+    // replaces a command of the same name
+    pub fn register(&mut self, name: &str, usage: &str, command: @Command) {
+        self.commands.retain(|c| c.name.as_slice() != name);
+        self.commands.push(Registered { name: name.to_owned(), usage: usage.to_owned(), item: command });
+    }
+
+    fn command(&self, name: &str) -> Option<@Command> {
+        self.commands.iter().find(|c| c.name.as_slice() == name).map(|c| c.item)
+    }
+
+    // one scrollback line per line of `text`, keeping only the characters
+    // the font has
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            self.lines.push(line.chars().filter(|&c| c >= ' ' && c <= '~').collect());
+            if self.lines.len() > SCROLLBACK {
+                self.lines.shift();
+            }
+        }
+    }
+
+    pub fn run(&mut self, line: &str, scope: &mut CommandScope) {
+        if !self.try_run(line, scope) {
+            let name = line.words().next().unwrap();
+            self.print(format!("unknown command {:s}, try help", name));
+        }
+    }
+
+    // false if neither the console nor `scope` know the line's command
+    fn try_run(&mut self, line: &str, scope: &mut CommandScope) -> bool {
+        let words: ~[&str] = line.words().collect();
+        let result = match words.as_slice() {
+            [] => return true,
+            ["help"] => Ok(self.help(scope)),
+            ["exec", path] => {
+                self.run_script(path, scope);
+                return true;
+            },
+            [name, ..args] => match self.command(name) {
+                Some(c) => c.run(args),
+                None => match scope.run_command(words) {
+                    Some(result) => result,
+                    None => return false
+                }
+            }
+        };
+        match result {
+            Ok(text) => for l in text.lines() {
+                self.print(l);
+            },
+            Err(e) => self.print(format!("error: {:s}", e))
+        }
+        true
+    }
+
+    fn help(&self, scope: &CommandScope) -> ~str {
+        let mut lines = ~[~"help", ~"exec FILE"];
+        for c in self.commands.iter() {
+            lines.push(format!("{:s} {:s}", c.name, c.usage));
+        }
+        lines.push_all_move(scope.command_help());
+        lines.connect("\n")
+    }
+
+    // lines starting with # are comments
+    pub fn run_script(&mut self, path: &str, scope: &mut CommandScope) {
+        let file = io_error::cond.trap(|_| ()).inside(|| File::open(&Path::new(path)));
+        let mut file = match file {
+            Some(file) => BufferedReader::new(file),
+            None => {
+                self.print(format!("can't read {:s}", path));
+                return;
+            }
+        };
+        loop {
+            let line = match read_line(&mut file) {
+                Some(line) => line,
+                None => break
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            if !self.try_run(line, scope) {
+                self.waiting.push(line.to_owned());
+            }
+        }
+    }
+
+    // runs the script lines whose command has come along since, called
+    // every frame
+    pub fn run_waiting(&mut self, scope: &mut CommandScope) {
+        if self.waiting.is_empty() {
+            return;
+        }
+        for line in std::util::replace(&mut self.waiting, ~[]).move_iter() {
+            if !self.try_run(line, scope) {
+                self.waiting.push(line);
+            }
+        }
+    }
+
+    // true if the console took the key, holding a key down only repeats
+    // editing and history keys
+    pub fn key_pressed(&mut self, key: glfw::Key, repeat: bool, scope: &mut CommandScope) -> bool {
+        if key == glfw::KeyGraveAccent {
+            if !repeat {
+                self.open = !self.open;
+            }
+            return true;
+        }
+        if !self.open {
+            return false;
+        }
+        match key {
+            glfw::KeyEscape if !repeat => self.open = false,
+            glfw::KeyEnter if !repeat => {
+                let line = std::util::replace(&mut self.input, ~"");
+                self.print(format!("> {:s}", line));
+                if !line.trim().is_empty() {
+                    self.history.push(line.clone());
+                }
+                self.history_pos = None;
+                self.run(line, scope);
+            },
+            glfw::KeyBackspace => if !self.input.is_empty() {
+                self.input.pop_char();
+            },
+            glfw::KeyUp if !self.history.is_empty() => {
+                let pos = match self.history_pos {
+                    Some(pos) if pos > 0 => pos - 1,
+                    Some(pos) => pos,
+                    None => self.history.len() - 1
+                };
+                self.history_pos = Some(pos);
+                self.input = self.history[pos].clone();
+            },
+            glfw::KeyDown => match self.history_pos {
+                Some(pos) if pos + 1 < self.history.len() => {
+                    self.history_pos = Some(pos + 1);
+                    self.input = self.history[pos + 1].clone();
+                },
+                _ => {
+                    self.history_pos = None;
+                    self.input = ~"";
+                }
+            },
+            _ => ()
+        }
+        true
+    }
+
+    pub fn char_typed(&mut self, c: char) {
+        // the key that opens the console types one too
+        if self.open && c != '`' && c >= ' ' && c <= '~' {
+            self.input.push_char(c);
+        }
+    }
+
+    // over the top of the arena, drawn after everything else
+    pub fn render(&self, rs: &RenderSystem) {
+        if !self.open {
+            return;
+        }
+        let line_height = 2.4*CHAR_WIDTH;
+        let height = (SHOWN_LINES + 1) as f64*line_height + 0.05;
+        rs.draw_quad(2.0, 3.0 - height/2.0, 4.4, height, [0.0, 0.0, 0.0, 0.85], None);
+        let first = self.lines.len() - self.lines.len().min(&SHOWN_LINES);
+        for (i, line) in self.lines.slice_from(first).iter().enumerate() {
+            rs.draw_label(0.0, 3.0 - (i as f64 + 0.5)*line_height, CHAR_WIDTH, *line, [0.8, 0.8, 0.8, 0.0]);
+        }
+        let prompt = format!("> {:s}_", self.input);
+        rs.draw_label(0.0, 3.0 - (SHOWN_LINES as f64 + 0.5)*line_height, CHAR_WIDTH, prompt, [1.0, 1.0, 1.0, 0.0]);
+    }
+}
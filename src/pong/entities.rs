@@ -1,85 +1,276 @@
 use components::*;
+use collision::{LAYER_BALL,LAYER_PADDLE,LAYER_WALL};
 
 //ENTITY CONSTRUCTORS
+#[deriving(Eq)]
 pub enum PaddleSide {
     RIGHT,
     LEFT
 }
 
-pub fn new_ball() -> @Components {
-    @Components {
-        position: Some(@mut Position { x: 2.0, y: 1.5 }),
-        horiz_velocity: Some(@mut HorizVelocity { x: 1.0/60.0 }),
-        vert_velocity: Some(@mut VertVelocity { y: 0.0 }),
-        sprite: Some(@mut Sprite {
+impl PaddleSide {
+    // for indexing per-side arrays
+    pub fn index(&self) -> uint {
+        match *self {
+            LEFT => 0,
+            RIGHT => 1
+        }
+    }
+
+    pub fn opponent(&self) -> PaddleSide {
+        match *self {
+            LEFT => RIGHT,
+            RIGHT => LEFT
+        }
+    }
+}
+
+pub enum EdgeSide {
+    TOP,
+    BOTTOM
+}
+
+pub fn new_ball() -> Components {
+    Components {
+        position: Some(Position { x: 2.0, y: 1.5 }),
+        velocity: Some(Velocity { x: 1.0/60.0, y: 0.0 }),
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
             x_size: 0.10,
             y_size: 0.20,
             color: [0.8, 0.7, 0.3, 0.0],
-            texture: Some(texture_from_char('@'))
+            texture: Some(texture_from_char('@')),
+            visible: true,
+            scale: (1.0, 1.0)
         }),
+        animation: None,
+        tweens: Some(Tweens::new()),
+        collider: Some(Collider {
+            shape: CircleShape(0.06),
+            layer: LAYER_BALL,
+            mask: LAYER_PADDLE | LAYER_WALL
+        }),
+        bounds: None,
+        text: None,
     }
 }
 
-pub fn new_paddle(side: PaddleSide) -> @Components {
+pub fn new_paddle(side: PaddleSide) -> Components {
     let xpos = match side {
         RIGHT => 3.9,
         LEFT => 0.1
     };
-    @Components {
-        position: Some(@mut Position { x: xpos, y: 1.5 }),
-        horiz_velocity: None,
-        vert_velocity: Some(@mut VertVelocity { y: 0.0 }),
-        sprite: Some(@mut Sprite {
+    Components {
+        position: Some(Position { x: xpos, y: 1.5 }),
+        velocity: Some(Velocity { x: 0.0, y: 0.0 }),
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
             x_size: 0.1,
             y_size: 0.4,
             color: [xpos/4.0, 1.0-(xpos/4.0), 0.3, 1.0],
-            texture: None
+            texture: None,
+            visible: true,
+            scale: (1.0, 1.0)
+        }),
+        animation: None,
+        tweens: Some(Tweens::new()),
+        collider: Some(Collider {
+            shape: AabbShape(0.1, 0.4),
+            layer: LAYER_PADDLE,
+            mask: 0
         }),
+        bounds: Some(MovementBounds {
+            min_y: 0.2,
+            max_y: 2.8,
+            ease_distance: 0.1
+        }),
+        text: None,
     }
 }
 
-pub fn new_background_2() -> @Components {
-    @Components {
-        position: Some(@mut Position { x: 2.0, y: 1.5 }),
-        horiz_velocity: None,
-        vert_velocity: None,
-        sprite: Some(@mut Sprite {
+pub fn new_background_2() -> Components {
+    Components {
+        position: Some(Position { x: 2.0, y: 1.5 }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
             x_size: 3.0,
             y_size: 2.0,
             color: [0.0, 0.0, 0.0, 0.3],
-            texture: None
+            texture: None,
+            visible: true,
+            scale: (1.0, 1.0)
+        }),
+        animation: None,
+        tweens: None,
+        collider: None,
+        bounds: None,
+        text: None,
+    }
+}
+
+// darkens whatever is drawn below it, eg. the game behind the pause menu
+pub fn new_overlay() -> Components {
+    Components {
+        position: Some(Position { x: 2.0, y: 1.5 }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
+            x_size: 4.0,
+            y_size: 3.0,
+            color: [0.0, 0.0, 0.0, 0.6],
+            texture: None,
+            visible: true,
+            scale: (1.0, 1.0)
         }),
+        animation: None,
+        tweens: None,
+        collider: None,
+        bounds: None,
+        text: None,
     }
 }
 
-pub fn new_background() -> @Components {
-    @Components {
-        position: Some(@mut Position { x: 2.0, y: 1.5 }),
-        horiz_velocity: None,
-        vert_velocity: None,
-        sprite: Some(@mut Sprite {
+pub fn new_background() -> Components {
+    Components {
+        position: Some(Position { x: 2.0, y: 1.5 }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
             x_size: 4.0,
             y_size: 3.0,
             color: [0.45, 0.4, 1.0, 1.0],
-            texture: None
+            texture: None,
+            visible: true,
+            scale: (1.0, 1.0)
         }),
+        animation: None,
+        tweens: None,
+        collider: None,
+        bounds: None,
+        text: None,
     }
 }
 
-pub fn new_score_counter(side: PaddleSide) -> @Components {
+pub fn new_score_counter(side: PaddleSide) -> Components {
     let xpos = match side {
         RIGHT => 2.5,
         LEFT => 1.5
     };
-    @Components {
-        position: Some(@mut Position { x: xpos, y: 2.5 }),
-        horiz_velocity: None,
-        vert_velocity: None,
-        sprite: Some(@mut Sprite {
+    Components {
+        position: Some(Position { x: xpos, y: 2.5 }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
             x_size: 0.3,
             y_size: 0.6,
             color: [1.0, 1.0, 1.0, 0.0],
-            texture: Some(texture_from_char('0'))
+            texture: None,
+            visible: true,
+            scale: (1.0, 1.0)
+        }),
+        animation: None,
+        tweens: Some(Tweens::new()),
+        collider: None,
+        bounds: None,
+        text: Some(Text { text: ~"0" }),
+    }
+}
+
+// invisible walls just outside the top and bottom of the arena
+pub fn new_edge(side: EdgeSide) -> Components {
+    let ypos = match side {
+        TOP => 3.5,
+        BOTTOM => -0.5
+    };
+    Components {
+        position: Some(Position { x: 2.0, y: ypos }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: None,
+        animation: None,
+        tweens: None,
+        collider: Some(Collider {
+            shape: AabbShape(200.0, 1.0),
+            layer: LAYER_WALL,
+            mask: 0
+        }),
+        bounds: None,
+        text: None,
+    }
+}
+
+// shows the 3-2-1 countdown before a serve
+pub fn new_serve_countdown() -> Components {
+    Components {
+        position: Some(Position { x: 2.0, y: 1.5 }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
+            x_size: 0.4,
+            y_size: 0.8,
+            color: [1.0, 1.0, 1.0, 0.0],
+            texture: Some(texture_from_char('3')),
+            visible: false,
+            scale: (1.0, 1.0)
+        }),
+        animation: Some(Animation::from_str("321", 60, false)),
+        tweens: Some(Tweens::new()),
+        collider: None,
+        bounds: None,
+        text: None,
+    }
+}
+
+pub fn new_text(x: f64, y: f64, char_width: f64, text: &str, color: [f64, ..4]) -> Components {
+    Components {
+        position: Some(Position { x: x, y: y }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
+            x_size: char_width,
+            y_size: char_width*2.0,
+            color: color,
+            texture: None,
+            visible: true,
+            scale: (1.0, 1.0)
+        }),
+        animation: None,
+        tweens: Some(Tweens::new()),
+        collider: None,
+        bounds: None,
+        text: Some(Text { text: text.to_owned() }),
+    }
+}
+
+// a plain rectangle, eg. the replay timeline
+pub fn new_rect(x: f64, y: f64, x_size: f64, y_size: f64, color: [f64, ..4]) -> Components {
+    Components {
+        position: Some(Position { x: x, y: y }),
+        velocity: None,
+        acceleration: None,
+        damping: None,
+        sprite: Some(Sprite {
+            x_size: x_size,
+            y_size: y_size,
+            color: color,
+            texture: None,
+            visible: true,
+            scale: (1.0, 1.0)
         }),
+        animation: None,
+        tweens: None,
+        collider: None,
+        bounds: None,
+        text: None,
     }
 }
@@ -0,0 +1,194 @@
+// TRAINING ENVIRONMENT
+// runs matches without a window for training paddle agents, one paddle is
+// driven through `step` and the other by a bot
+extern mod std;
+use ai::{BotAi,PredictorAi,BotLevel,Normal};
+use components::Components;
+use entities::{PaddleSide,LEFT};
+use game::{MatchWorld,new_match_world,seeded_rng};
+use globalsystems::{GlobalSystem,BotInputSystem};
+use input::{InputSnapshot,PADDLE_SPEED};
+use resources::{MatchRules,ScorerServes,Countdown,Integrator,ExplicitEuler};
+
+pub struct EnvConfig {
+    // the paddle driven by the agent
+    side: PaddleSide,
+    opponent_ai: BotAi,
+    opponent_level: BotLevel,
+    rules: MatchRules,
+    integrator: Integrator,
+    // ticks before each serve, the game uses 180
+    serve_countdown: uint,
+    // an episode ends when the match is over or after this many steps
+    max_steps: uint,
+    // when set, observations also include a frame of this many pixels
+    frame_size: Option<(uint, uint)>
+}
+
+impl EnvConfig {
+    pub fn new() -> EnvConfig {
+        EnvConfig {
+            side: LEFT,
+            opponent_ai: PredictorAi,
+            opponent_level: Normal,
+            rules: MatchRules::new(),
+            integrator: ExplicitEuler,
+            serve_countdown: 3,
+            max_steps: 60*60*5,
+            frame_size: None
+        }
+    }
+}
+
+// number of values in Observation::state
+pub static STATE_SIZE: uint = 8;
+
+pub struct Observation {
+    // ball x, y, vx, vy, then y and vy of the agent's paddle and of the
+    // opponent's, in arena units and arena units per tick
+    state: ~[f64],
+    // row-major from the top left, 255 where the ball or a paddle is
+    frame: Option<~[u8]>
+}
+
+pub struct PongEnv {
+    config: EnvConfig,
+    game: Option<MatchWorld>,
+    steps: uint,
+    // total points of the agent and the opponent after the last step
+    points: (uint, uint)
+}
+
+impl PongEnv {
+    pub fn new(config: EnvConfig) -> PongEnv {
+        PongEnv { config: config, game: None, steps: 0, points: (0, 0) }
+    }
+
+    // starts a new match, the same seed gives the same match for the same actions
+    pub fn reset(&mut self, seed: u32) -> Observation {
+        let mut game = new_match_world(self.config.rules, ScorerServes, self.config.integrator, seeded_rng(seed));
+        game.world.resources.serve.countdown_ticks = self.config.serve_countdown;
+        game.world.resources.serve.state = Countdown(self.config.serve_countdown);
+
+        let side = self.config.side.opponent();
+        let paddle = game.paddle(side);
+        let strategy = self.config.opponent_ai.strategy(paddle, game.paddle(side.opponent()), false);
+        game.world.resources.bot_difficulty[side.index()] = self.config.opponent_level.difficulty();
+        let bis = ~BotInputSystem::new(side, paddle, game.ball, strategy);
+        game.world.global_systems.push(bis as ~GlobalSystem);
+
+        self.game = Some(game);
+        self.steps = 0;
+        self.points = (0, 0);
+        self.observe()
+    }
+
+    // `action` is the agent's paddle velocity as a fraction of the players'
+    // top speed, clamped to [-1, 1]
+    // the reward is 1.0 for a point won and -1.0 for a point lost
+    pub fn step(&mut self, action: f64) -> (Observation, f64, bool) {
+        let side = self.config.side;
+        let (reward, over) = match self.game {
+            Some(ref mut game) => {
+                let paddle = game.paddle(side);
+                game.world.entities[paddle].velocity.get_mut_ref().y = action.max(&-1.0).min(&1.0)*PADDLE_SPEED;
+                game.world.process(&InputSnapshot::none());
+                let m = &game.world.resources.match_state;
+                let points = (m.total_points[side.index()], m.total_points[side.opponent().index()]);
+                let (won, lost) = points;
+                let (prev_won, prev_lost) = self.points;
+                self.points = points;
+                ((won - prev_won) as f64 - (lost - prev_lost) as f64, m.is_over())
+            },
+            None => fail!("PongEnv::step called before reset")
+        };
+        self.steps += 1;
+        let done = over || self.steps >= self.config.max_steps;
+        (self.observe(), reward, done)
+    }
+
+    fn observe(&self) -> Observation {
+        let game = match self.game {
+            Some(ref game) => game,
+            None => fail!("PongEnv has no match, call reset first")
+        };
+        let side = self.config.side;
+        let entities = game.world.entities.as_slice();
+        let (ball_pos, ball_vel) = (entities[game.ball].position.unwrap(), entities[game.ball].velocity.unwrap());
+        let mut state = ~[ball_pos.x, ball_pos.y, ball_vel.x, ball_vel.y];
+        for &paddle in [game.paddle(side), game.paddle(side.opponent())].iter() {
+            state.push(entities[paddle].position.unwrap().y);
+            state.push(entities[paddle].velocity.unwrap().y);
+        }
+        Observation {
+            state: state,
+            frame: self.config.frame_size.map(|size| render_frame(entities, size))
+        }
+    }
+}
+
+// draws the visible sprites of everything that collides, ie. the ball and the
+// paddles, into a grayscale frame of the whole arena
+pub fn render_frame(entities: &[Components], size: (uint, uint)) -> ~[u8] {
+    let (width, height) = size;
+    let mut frame = std::vec::from_elem(width*height, 0u8);
+    let (sx, sy) = (width as f64/4.0, height as f64/3.0);
+    for entity in entities.iter() {
+        match (entity.position, entity.sprite, entity.collider) {
+            (Some(pos), Some(sprite), Some(_)) if sprite.visible => {
+                let to_col = |x: f64| (x*sx).max(&0.0).min(&(width as f64)) as uint;
+                let to_row = |y: f64| ((3.0 - y)*sy).max(&0.0).min(&(height as f64)) as uint;
+                let (x0, x1) = (to_col(pos.x - sprite.x_size/2.0), to_col(pos.x + sprite.x_size/2.0));
+                let (y0, y1) = (to_row(pos.y + sprite.y_size/2.0), to_row(pos.y - sprite.y_size/2.0));
+                // anything smaller than a pixel still shows up as one
+                for row in range(y0, (y0 + 1).max(&y1).min(&height)) {
+                    for col in range(x0, (x0 + 1).max(&x1).min(&width)) {
+                        frame[row*width + col] = 255;
+                    }
+                }
+            },
+            (_, _, _) => ()
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod test {
+    extern mod extra;
+    use extra::test::BenchHarness;
+    use env::*;
+
+    static STEPS: uint = 6000;
+
+    // the same seed and actions give the same match, see PongEnv::reset
+    #[test]
+    fn same_seed_same_match() {
+        let mut a = PongEnv::new(EnvConfig::new());
+        let mut b = PongEnv::new(EnvConfig::new());
+        assert_eq!(a.reset(7).state, b.reset(7).state);
+        for i in range(0, STEPS) {
+            let action = if i % 120 < 60 { 1.0 } else { -1.0 };
+            let (obs_a, reward_a, done_a) = a.step(action);
+            let (obs_b, reward_b, done_b) = b.step(action);
+            assert_eq!(obs_a.state, obs_b.state);
+            assert_eq!((reward_a, done_a), (reward_b, done_b));
+            if done_a {
+                a.reset(i as u32);
+                b.reset(i as u32);
+            }
+        }
+    }
+
+    #[bench]
+    fn bench_step(bh: &mut BenchHarness) {
+        let mut env = PongEnv::new(EnvConfig::new());
+        env.reset(1);
+        bh.iter(|| {
+            let (_, _, done) = env.step(0.5);
+            if done {
+                env.reset(1);
+            }
+        });
+    }
+}
@@ -0,0 +1,305 @@
+// EXTERNAL BOTS
+// paddles driven by bots written in other languages. Every tick the game state
+// is sent to the bot as one line of JSON, eg.
+//   {"tick":12,"side":"left","ball":{"x":2.0,"y":1.5,"vx":0.016,"vy":0.0},
+//    "paddle":{"x":0.1,"y":1.5,"vy":0.0},"opponent":{"x":3.9,"y":1.5,"vy":0.0},
+//    "points":[0,0],"games":[0,0],"sets":[0,0]}
+// (own side first in the score arrays) and the bot answers with one line,
+//   {"tick":12,"move":0.5}
+// where move is the paddle velocity as a fraction of the top speed, up being
+// positive. Answers that don't arrive within the timeout are skipped and the
+// built-in bot moves the paddle for that tick.
+extern mod extra;
+extern mod std;
+use extra::json;
+use extra::time::precise_time_ns;
+use std::io::net::ip::SocketAddr;
+use std::io::net::tcp::TcpListener;
+use std::io::{Reader,Writer,Listener,Acceptor,PipeStream};
+use std::io::process::{Process,ProcessConfig,CreatePipe,InheritFd};
+use components::Components;
+use entities::{PaddleSide,LEFT,RIGHT};
+use globalsystems::{GlobalSystem,BotInputSystem};
+use input::{InputSnapshot,PADDLE_SPEED};
+use resources::{MatchState,Resources};
+
+// after this many ticks without an answer the bot is reported as stalled
+static STALL_TICKS: uint = 60;
+
+#[deriving(Clone)]
+pub enum BotLink {
+    // a command line to run, talked to over its stdin and stdout
+    ProcessLink(~str),
+    // an address to listen on for a single bot to connect to
+    TcpLink(~str)
+}
+
+impl BotLink {
+    // `tcp:ADDRESS:PORT` or a command line
+    pub fn from_spec(spec: &str) -> BotLink {
+        if spec.starts_with("tcp:") {
+            TcpLink(spec.slice_from(4).to_owned())
+        } else {
+            ProcessLink(spec.to_owned())
+        }
+    }
+}
+
+pub fn read_line<R: Reader>(r: &mut R) -> Option<~str> {
+    let mut bytes = ~[];
+    loop {
+        match r.read_byte() {
+            Some(b) if b == '\n' as u8 => break,
+            Some(b) => bytes.push(b),
+            None if bytes.is_empty() => return None,
+            None => break
+        }
+    }
+    Some(std::str::from_utf8_owned(bytes))
+}
+
+// writes the newest state waiting in `requests` and passes the answer on,
+// until either side goes away
+fn exchange_lines<S: Reader + Writer>(stream: &mut S, requests: &Port<~str>, replies: &Chan<~str>) {
+    loop {
+        let mut line = match requests.recv_opt() {
+            Some(line) => line,
+            None => return
+        };
+        // a slow bot skips states rather than falling further behind
+        loop {
+            match requests.try_recv() {
+                Some(newer) => line = newer,
+                None => break
+            }
+        }
+        stream.write(line.as_bytes());
+        stream.write(['\n' as u8]);
+        stream.flush();
+        match read_line(stream) {
+            Some(reply) => if !replies.try_send(reply) { return },
+            None => return
+        }
+    }
+}
+
+// a child process's stdout and stdin as one stream
+struct ChildPipes {
+    stdin: PipeStream,
+    stdout: PipeStream
+}
+
+impl Reader for ChildPipes {
+    fn read(&mut self, buf: &mut [u8]) -> Option<uint> {
+        self.stdout.read(buf)
+    }
+
+    fn eof(&mut self) -> bool {
+        self.stdout.eof()
+    }
+}
+
+impl Writer for ChildPipes {
+    fn write(&mut self, buf: &[u8]) {
+        self.stdin.write(buf)
+    }
+
+    fn flush(&mut self) {
+        self.stdin.flush()
+    }
+}
+
+fn run_process(command: &str, requests: &Port<~str>, replies: &Chan<~str>) {
+    let args: ~[~str] = command.words().map(|w| w.to_owned()).collect();
+    if args.is_empty() {
+        println!("extbot: empty bot command");
+        return;
+    }
+    // the bot's stderr goes straight to ours so that it can log
+    let io = [CreatePipe(true, false), CreatePipe(false, true), InheritFd(2)];
+    let config = ProcessConfig {
+        program: args[0].as_slice(),
+        args: args.slice_from(1),
+        env: None,
+        cwd: None,
+        io: io.as_slice()
+    };
+    let mut process = match Process::new(config) {
+        Some(process) => process,
+        None => {
+            println!("extbot: can't start `{:s}`", command);
+            return;
+        }
+    };
+    println!("extbot: started `{:s}`", command);
+    match (process.io[0].take(), process.io[1].take()) {
+        (Some(stdin), Some(stdout)) => {
+            let mut pipes = ChildPipes { stdin: stdin, stdout: stdout };
+            exchange_lines(&mut pipes, requests, replies);
+        },
+        (_, _) => println!("extbot: no pipes to `{:s}`", command)
+    }
+}
+
+fn run_tcp(address: &str, requests: &Port<~str>, replies: &Chan<~str>) {
+    let addr: SocketAddr = match from_str(address) {
+        Some(addr) => addr,
+        None => {
+            println!("extbot: bad address {:s}", address);
+            return;
+        }
+    };
+    let mut acceptor = match TcpListener::bind(addr).and_then(|l| l.listen()) {
+        Some(acceptor) => acceptor,
+        None => {
+            println!("extbot: can't listen on {:s}", address);
+            return;
+        }
+    };
+    println!("extbot: waiting for a bot on {:s}", address);
+    match acceptor.accept() {
+        Some(mut stream) => {
+            println!("extbot: bot connected on {:s}", address);
+            exchange_lines(&mut stream, requests, replies);
+        },
+        None => println!("extbot: no bot connected on {:s}", address)
+    }
+}
+
+// runs in its own task so that a bot can never block the game
+fn run_link(link: BotLink, requests: Port<~str>, replies: Chan<~str>) {
+    match link {
+        ProcessLink(command) => run_process(command, &requests, &replies),
+        TcpLink(address) => run_tcp(address, &requests, &replies)
+    }
+}
+
+fn parse_reply(line: &str) -> Option<(uint, f64)> {
+    match json::from_str(line) {
+        Ok(json::Object(obj)) => match (obj.find(&~"tick"), obj.find(&~"move")) {
+            (Some(&json::Number(tick)), Some(&json::Number(m))) => Some((tick as uint, m)),
+            (_, _) => None
+        },
+        _ => None
+    }
+}
+
+// the game's end of the connection to an external bot
+pub struct ExternalBot {
+    requests: Chan<~str>,
+    replies: Port<~str>,
+    timeout_ns: u64,
+    // ticks in a row without an answer
+    misses: uint
+}
+
+impl ExternalBot {
+    pub fn connect(link: BotLink, timeout_ms: u64) -> ExternalBot {
+        let (request_port, request_chan): (Port<~str>, Chan<~str>) = std::comm::Chan::new();
+        let (reply_port, reply_chan): (Port<~str>, Chan<~str>) = std::comm::Chan::new();
+        do std::task::spawn {
+            run_link(link, request_port, reply_chan);
+        }
+        ExternalBot { requests: request_chan, replies: reply_port, timeout_ns: timeout_ms*1000000, misses: 0 }
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.misses >= STALL_TICKS
+    }
+
+    // sends the state for `tick` and waits for the bot's move, None if it
+    // didn't answer in time
+    pub fn exchange(&mut self, tick: uint, state: ~str) -> Option<f64> {
+        let deadline = precise_time_ns() + self.timeout_ns;
+        let mut answer = None;
+        if self.requests.try_send(state) {
+            while answer.is_none() && precise_time_ns() < deadline {
+                match self.replies.try_recv() {
+                    // answers to earlier ticks arrive late and are dropped
+                    Some(line) => match parse_reply(line) {
+                        Some((t, m)) if t == tick => answer = Some(m),
+                        Some(_) => (),
+                        None => println!("extbot: can't parse `{:s}`", line)
+                    },
+                    None => std::io::timer::sleep(1)
+                }
+            }
+        }
+        match answer {
+            Some(_) => {
+                if self.is_stalled() {
+                    println!("extbot: bot is answering again");
+                }
+                self.misses = 0;
+            },
+            None => {
+                self.misses += 1;
+                if self.misses == STALL_TICKS {
+                    println!("extbot: bot stalled, the built-in bot takes over");
+                }
+            }
+        }
+        answer
+    }
+}
+
+// moves the paddle as told by an external bot, the built-in bot runs
+// alongside it and its move is kept whenever the external one is late
+pub struct ExternalBotSystem {
+    side: PaddleSide,
+    // entity ids
+    paddle: uint,
+    opponent: uint,
+    ball: uint,
+    bot: ExternalBot,
+    fallback: BotInputSystem,
+    tick: uint
+}
+
+impl ExternalBotSystem {
+    pub fn new(side: PaddleSide, paddle: uint, opponent: uint, ball: uint,
+               bot: ExternalBot, fallback: BotInputSystem) -> ExternalBotSystem {
+        ExternalBotSystem {
+            side: side,
+            paddle: paddle,
+            opponent: opponent,
+            ball: ball,
+            bot: bot,
+            fallback: fallback,
+            tick: 0
+        }
+    }
+
+    fn state_json(&self, entities: &[Components], m: &MatchState) -> ~str {
+        let (bp, bv) = (entities[self.ball].position.unwrap(), entities[self.ball].velocity.unwrap());
+        let (pp, pv) = (entities[self.paddle].position.unwrap(), entities[self.paddle].velocity.unwrap());
+        let (op, ov) = (entities[self.opponent].position.unwrap(), entities[self.opponent].velocity.unwrap());
+        let (i, o) = (self.side.index(), self.side.opponent().index());
+        let side = match self.side {
+            LEFT => "left",
+            RIGHT => "right"
+        };
+        format!("\\{\"tick\":{},\"side\":\"{}\",\
+                 \"ball\":\\{\"x\":{},\"y\":{},\"vx\":{},\"vy\":{}\\},\
+                 \"paddle\":\\{\"x\":{},\"y\":{},\"vy\":{}\\},\
+                 \"opponent\":\\{\"x\":{},\"y\":{},\"vy\":{}\\},\
+                 \"points\":[{},{}],\"games\":[{},{}],\"sets\":[{},{}]\\}",
+                self.tick, side,
+                bp.x, bp.y, bv.x, bv.y,
+                pp.x, pp.y, pv.y,
+                op.x, op.y, ov.y,
+                m.points[i], m.points[o], m.games[i], m.games[o], m.sets[i], m.sets[o])
+    }
+}
+
+impl GlobalSystem for ExternalBotSystem {
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, input: &InputSnapshot) -> () {
+        self.fallback.process(entities, resources, input);
+        self.tick += 1;
+        let state = self.state_json(entities, &resources.match_state);
+        match self.bot.exchange(self.tick, state) {
+            Some(m) => entities[self.paddle].velocity.get_mut_ref().y = m.max(&-1.0).min(&1.0)*PADDLE_SPEED,
+            None => ()
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// MATCH SETUP
+extern mod std;
+use std::rand::{Rng,SeedableRng,XorShiftRng};
+use collision::*;
+use entities::*;
+use globalsystems::*;
+use resources::*;
+use systems::*;
+use world::World;
+
+// a world set up for a match, along with the ids of the entities that
+// whoever drives it needs to reach into
+pub struct MatchWorld {
+    // has no input systems yet, those are up to the caller
+    world: World,
+    ball: uint,
+    left_paddle: uint,
+    right_paddle: uint
+}
+
+impl MatchWorld {
+    pub fn paddle(&self, side: PaddleSide) -> uint {
+        match side {
+            LEFT => self.left_paddle,
+            RIGHT => self.right_paddle
+        }
+    }
+}
+
+// the same seed always gives the same sequence, for reproducible matches
+pub fn seeded_rng(seed: u32) -> XorShiftRng {
+    SeedableRng::from_seed([seed, 0x193a6754, 0xa8a7d469, 0x97830e05])
+}
+
+pub fn new_match_world(rules: MatchRules, serve_rule: ServeRule, integrator: Integrator, rng: XorShiftRng) -> MatchWorld {
+    let mut world: World = World::new();
+    world.resources.physics.integrator = integrator;
+    world.resources.serve = ServeControl::new(LEFT, serve_rule);
+    world.resources.match_state = MatchState::new(rules);
+    world.resources.rng = rng;
+
+    world.add(new_background());
+    world.add(new_background_2());
+    let left_score_counter = world.add(new_score_counter(LEFT));
+    let right_score_counter = world.add(new_score_counter(RIGHT));
+    let left_paddle = world.add(new_paddle(LEFT));
+    let right_paddle = world.add(new_paddle(RIGHT));
+    let ball = world.add(new_ball());
+    let serve_countdown = world.add(new_serve_countdown());
+    let match_hud = world.add(new_text(2.0, 2.9, 0.05, "", [1.0, 1.0, 1.0, 0.0]));
+    world.add(new_edge(TOP));
+    world.add(new_edge(BOTTOM));
+    world.systems.push(~BoundsSystem as ~System);
+    world.systems.push(~MovementSystem as ~System);
+    world.systems.push(~CollisionSystem as ~System);
+    world.systems.push(~EdgeCollisionSystem as ~System);
+    world.systems.push(~PaddleCollisionSystem as ~System);
+    world.systems.push(~ScoreCollisionSystem as ~System);
+    world.systems.push(~AnimationSystem as ~System);
+    world.systems.push(~TweenSystem as ~System);
+
+    let srvs = ~ServeSystem {
+        ball: ball,
+        left_paddle: left_paddle,
+        right_paddle: right_paddle,
+        countdown: serve_countdown
+    };
+    world.global_systems.push(srvs as ~GlobalSystem);
+    world.global_systems.push(~ScoreUpdateSystem::new(LEFT, left_score_counter) as ~GlobalSystem);
+    world.global_systems.push(~ScoreUpdateSystem::new(RIGHT, right_score_counter) as ~GlobalSystem);
+    world.global_systems.push(~MatchSystem { hud: match_hud } as ~GlobalSystem);
+
+    MatchWorld {
+        world: world,
+        ball: ball,
+        left_paddle: left_paddle,
+        right_paddle: right_paddle
+    }
+}
+
+// the ball's settings the console can change, speeds are in arena units per
+// second, unlike in BallPhysics
+pub enum BallSetting {
+    BallSpeed,
+    BallMaxSpeed,
+    BallSpin
+}
+
+pub static BALL_SETTINGS: [BallSetting, ..3] = [BallSpeed, BallMaxSpeed, BallSpin];
+
+impl BallSetting {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BallSpeed => "ball.speed",
+            BallMaxSpeed => "ball.max_speed",
+            BallSpin => "ball.spin"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BallSetting> {
+        BALL_SETTINGS.iter().find(|s| s.name() == name).map(|&s| s)
+    }
+}
+
+impl MatchWorld {
+    // an extra ball scores like the first one and then stays in the middle
+    pub fn spawn_ball(&mut self) {
+        let mut ball = new_ball();
+        {
+            let resources = &mut self.world.resources;
+            let direction = if resources.rng.gen() { 1.0 } else { -1.0 };
+            let (vx, vy) = resources.ball_physics.serve_velocity(direction, &mut resources.rng);
+            let vel = ball.velocity.get_mut_ref();
+            vel.x = vx;
+            vel.y = vy;
+        }
+        self.world.add(ball);
+    }
+
+    // the points of the current game
+    pub fn set_points(&mut self, side: PaddleSide, points: uint) {
+        self.world.resources.match_state.points[side.index()] = points;
+    }
+
+    pub fn ball_setting(&self, setting: BallSetting) -> f64 {
+        let physics = &self.world.resources.ball_physics;
+        match setting {
+            BallSpeed => physics.serve_speed*60.0,
+            BallMaxSpeed => physics.max_speed*60.0,
+            BallSpin => physics.spin
+        }
+    }
+
+    pub fn set_ball_setting(&mut self, setting: BallSetting, value: f64) {
+        let physics = &mut self.world.resources.ball_physics;
+        match setting {
+            BallSpeed => {
+                // the ball in play changes speed too, not only the next serve
+                let vel = self.world.entities[self.ball].velocity.get_mut_ref();
+                let speed = (vel.x*vel.x + vel.y*vel.y).sqrt();
+                if speed > 0.0 {
+                    vel.x *= value/60.0/speed;
+                    vel.y *= value/60.0/speed;
+                }
+                physics.serve_speed = value/60.0;
+                physics.max_speed = physics.max_speed.max(&(value/60.0));
+            },
+            BallMaxSpeed => physics.max_speed = value/60.0,
+            BallSpin => physics.spin = value
+        }
+    }
+}
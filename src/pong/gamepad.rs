@@ -0,0 +1,137 @@
+// GAMEPADS
+extern mod glfw;
+extern mod std;
+
+use input::{Action,Serve,Pause,PLAYERS,PADDLE_SPEED};
+
+// how a gamepad's stick and buttons map to paddle movement and actions,
+// shared by all gamepads
+pub struct GamepadConfig {
+    // index of the stick axis that moves the paddle
+    axis: uint,
+    // most pads report stick up as negative
+    invert: bool,
+    // fraction of the axis range around the center that is ignored
+    deadzone: f64,
+    // exponent applied to the stick deflection past the deadzone, 1.0 is
+    // linear and larger values give finer control near the center
+    curve: f64,
+    serve_button: uint,
+    pause_button: uint
+}
+
+impl GamepadConfig {
+    pub fn new() -> GamepadConfig {
+        GamepadConfig {
+            axis: 1,
+            invert: true,
+            deadzone: 0.15,
+            curve: 2.0,
+            serve_button: 0,
+            pause_button: 7
+        }
+    }
+
+    // maps a raw axis value in [-1, 1] to a paddle velocity
+    pub fn response(&self, value: f64) -> f64 {
+        let value = if self.invert { -value } else { value };
+        let magnitude = std::num::abs(value).min(&1.0);
+        if magnitude <= self.deadzone {
+            return 0.0;
+        }
+        let scaled = ((magnitude - self.deadzone)/(1.0 - self.deadzone)).powf(&self.curve);
+        let sign = if value < 0.0 { -1.0 } else { 1.0 };
+        sign*scaled*PADDLE_SPEED
+    }
+}
+
+struct Gamepad {
+    joystick: glfw::Joystick,
+    connected: bool,
+    axes: ~[f32],
+    buttons: ~[bool],
+    // button states of the previous poll, for detecting presses
+    held: ~[bool]
+}
+
+impl Gamepad {
+    fn is_down(&self, button: uint) -> bool {
+        button < self.buttons.len() && self.buttons[button]
+    }
+
+    fn was_pressed(&self, button: uint) -> bool {
+        self.is_down(button) && !(button < self.held.len() && self.held[button])
+    }
+}
+
+// one gamepad slot per player, polled once per frame so that pads can be
+// plugged in and out while the game runs
+pub struct Gamepads {
+    pads: ~[Gamepad],
+    config: GamepadConfig
+}
+
+impl Gamepads {
+    pub fn new(config: GamepadConfig) -> Gamepads {
+        let joysticks = [glfw::Joystick1, glfw::Joystick2];
+        let mut pads = ~[];
+        for player in range(0, PLAYERS) {
+            pads.push(Gamepad { joystick: joysticks[player], connected: false, axes: ~[], buttons: ~[], held: ~[] });
+        }
+        Gamepads { pads: pads, config: config }
+    }
+
+    pub fn is_connected(&self, player: uint) -> bool {
+        self.pads[player].connected
+    }
+
+    // refreshes the state of every pad and returns the (player, action)
+    // pairs whose buttons were pressed since the last poll
+    pub fn poll(&mut self) -> ~[(uint, Action)] {
+        let mut pressed = ~[];
+        for (player, pad) in self.pads.mut_iter().enumerate() {
+            let present = pad.joystick.is_present();
+            if present != pad.connected {
+                if present {
+                    println!("gamepad: {:s} connected for player {:u}", pad.joystick.get_name(), player + 1);
+                } else {
+                    println!("gamepad: disconnected for player {:u}", player + 1);
+                }
+                pad.connected = present;
+            }
+            pad.held = std::util::replace(&mut pad.buttons, ~[]);
+            if present {
+                pad.axes = pad.joystick.get_axes();
+                pad.buttons = pad.joystick.get_buttons().iter().map(|&b| b != 0).collect();
+            } else {
+                pad.axes = ~[];
+            }
+        }
+        for (player, pad) in self.pads.iter().enumerate() {
+            if pad.was_pressed(self.config.serve_button) {
+                pressed.push((player, Serve));
+            }
+            if pad.was_pressed(self.config.pause_button) {
+                pressed.push((player, Pause));
+            }
+        }
+        pressed
+    }
+
+    pub fn paddle_velocity(&self, player: uint) -> f64 {
+        let pad = &self.pads[player];
+        if self.config.axis < pad.axes.len() {
+            self.config.response(pad.axes[self.config.axis] as f64)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_held(&self, player: uint, action: Action) -> bool {
+        match action {
+            Serve => self.pads[player].is_down(self.config.serve_button),
+            Pause => self.pads[player].is_down(self.config.pause_button),
+            _ => false
+        }
+    }
+}
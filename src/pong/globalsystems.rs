@@ -1,66 +1,251 @@
-extern mod glfw;
 extern mod std;
-use components::{Components,texture_from_uint};
+use components::{Components,Tween,TweenSize,BackOut};
+use entities::{PaddleSide,LEFT,RIGHT};
+use resources::*;
+use input::{InputSnapshot,InputSource,Serve,combined_velocity};
+use ai::*;
 
 //GLOBAL SYSTEM DEFINITIONS
 pub trait GlobalSystem {
-    fn process(&mut self, window: &glfw::Window) -> ();
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, input: &InputSnapshot) -> ();
 }
 
 pub struct ScoreUpdateSystem {
-    paddle: @Components,
-    counter: @Components,
-    score: uint,
-    port: Port<uint>
+    side: PaddleSide,
+    counter: uint,
+    // the side's total points when the counter was last updated
+    shown: uint
+}
+
+impl ScoreUpdateSystem {
+    pub fn new(side: PaddleSide, counter: uint) -> ScoreUpdateSystem {
+        ScoreUpdateSystem { side: side, counter: counter, shown: 0 }
+    }
 }
 
 impl GlobalSystem for ScoreUpdateSystem {
-    fn process(&mut self, _: &glfw::Window) -> () {
-        loop {
-            match self.port.try_recv() {
-                Some(i) => {
-                    self.score += i;
-                }
-                None => break
-            }
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, _: &InputSnapshot) -> () {
+        let m = &resources.match_state;
+        let i = self.side.index();
+        let counter = &mut entities[self.counter];
+        counter.text.get_mut_ref().text = format!("{}", m.points[i]);
+        if m.total_points[i] != self.shown {
+            self.shown = m.total_points[i];
+            pop(counter, 1.5);
         }
-        self.counter.sprite.unwrap().texture = Some(texture_from_uint(self.score));
     }
 }
 
+// briefly grows the sprite so that a change is noticeable
+pub fn pop(entity: &mut Components, scale: f64) {
+    match *entity {
+        Components { sprite: Some(ref spr), tweens: Some(ref mut tweens), .. } => {
+            let rest = tweens.resting(TweenSize, [spr.x_size, spr.y_size, 0.0, 0.0]);
+            tweens.start(Tween::size((rest[0]*scale, rest[1]*scale), (rest[0], rest[1]), 20, BackOut));
+        },
+        _ => ()
+    }
+}
+
+// advances the match clock and keeps the game/set counts up to date, the
+// winner is shown by GameOverState
+pub struct MatchSystem {
+    hud: uint
+}
+
+impl GlobalSystem for MatchSystem {
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, _: &InputSnapshot) -> () {
+        resources.match_state.tick();
+        let m = &resources.match_state;
+        let mut hud = format!("GAMES {}-{}  SETS {}-{}", m.games[0], m.games[1], m.sets[0], m.sets[1]);
+        match m.rules.time_limit {
+            Some(limit) => {
+                let left = if m.elapsed < limit { (limit - m.elapsed)/60 } else { 0 };
+                hud.push_str(format!("  {}:{:02u}", left/60, left%60));
+            },
+            None => ()
+        }
+        if m.is_deuce() {
+            hud.push_str("  DEUCE");
+        }
+        entities[self.hud].text.get_mut_ref().text = hud;
+    }
+}
+
+// moves the paddle towards where its strategy wants it, held back by the
+// reaction time, aim and speed of the side's difficulty level
 pub struct BotInputSystem {
-    paddle: @Components,
-    ball: @Components
+    side: PaddleSide,
+    // entity ids
+    paddle: uint,
+    ball: uint,
+    strategy: ~BotStrategy,
+    // the last reaction_ticks + 1 views of the ball, oldest first
+    seen: ~[BallView],
+    // whether the ball was heading towards the paddle last tick
+    approaching: bool,
+    // added to the strategy's target, picked anew when the ball turns towards
+    // the paddle
+    error: f64
+}
+
+impl BotInputSystem {
+    pub fn new(side: PaddleSide, paddle: uint, ball: uint, strategy: ~BotStrategy) -> BotInputSystem {
+        BotInputSystem {
+            side: side,
+            paddle: paddle,
+            ball: ball,
+            strategy: strategy,
+            seen: ~[],
+            approaching: false,
+            error: 0.0
+        }
+    }
 }
 
 impl GlobalSystem for BotInputSystem {
-    fn process(&mut self, _: &glfw::Window) -> () {
-        let d = self.ball.position.unwrap().y - self.paddle.position.unwrap().y;
-        if std::num::abs(d) > 0.2 {
-            if d > 0.0 {
-                self.paddle.vert_velocity.unwrap().y = 1.5/60.0;
-            } else {
-                self.paddle.vert_velocity.unwrap().y = -1.5/60.0;
-            }
-        } else {
-            self.paddle.vert_velocity.unwrap().y = 0.0;
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, _: &InputSnapshot) -> () {
+        let difficulty = resources.bot_difficulty[self.side.index()];
+        self.seen.push(BallView::of(&entities[self.ball]));
+        if self.seen.len() > difficulty.reaction_ticks + 1 {
+            self.seen.shift();
         }
+        let view = self.seen[0].clone();
+
+        let paddle_pos = entities[self.paddle].position.unwrap();
+        let (paddle_hw, paddle_hh) = entities[self.paddle].collider.unwrap().half_extents();
+        let (ball_r, _) = entities[self.ball].collider.unwrap().half_extents();
+        // the face of the paddle that points into the court
+        let facing = if paddle_pos.x > 2.0 { -1.0 } else { 1.0 };
+        let hit_x = paddle_pos.x + facing*(paddle_hw + ball_r);
+
+        let approaching = view.vx*facing < 0.0;
+        if approaching && !self.approaching {
+            self.error = aim_error(&difficulty, paddle_hh + ball_r, &mut resources.rng);
+        }
+        self.approaching = approaching;
+
+        let court = Court::for_ball(&entities[self.ball]);
+        let target = self.strategy.target(&view, hit_x, &court, entities, &resources.ball_physics) + self.error;
+        resources.bot_targets[self.side.index()] = Some((hit_x, target));
+        let d = target - paddle_pos.y;
+        let max_speed = difficulty.max_speed;
+        entities[self.paddle].velocity.get_mut_ref().y = if std::num::abs(d) < difficulty.deadzone {
+            0.0
+        } else {
+            d.max(&-max_speed).min(&max_speed)
+        };
     }
 }
 
-pub struct KeyboardInputSystem {
-    paddle: @Components
+// the paddle is controlled by a human through any number of input sources,
+// eg. the keyboard and a gamepad, whichever is pushed hardest wins
+pub struct PlayerInputSystem {
+    paddle: uint,
+    side: PaddleSide,
+    sources: ~[~InputSource]
 }
 
-impl GlobalSystem for KeyboardInputSystem {
-    fn process(&mut self, window: &glfw::Window) -> () {
-        let mut dir = 0.0;
-        if window.get_key(glfw::KeyA) == glfw::Press {
-            dir += 1.0;
+impl GlobalSystem for PlayerInputSystem {
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, input: &InputSnapshot) -> () {
+        if self.sources.iter().any(|s| s.is_held(input, Serve)) {
+            resources.serve.request_launch(self.side);
         }
-        if window.get_key(glfw::KeyZ) == glfw::Press {
-            dir -= 1.0;
+        let paddle = &mut entities[self.paddle];
+        let y = paddle.position.unwrap().y;
+        paddle.velocity.get_mut_ref().y = combined_velocity(self.sources, input, y);
+    }
+}
+
+// runs the serve state machine: holds the ball in front of the server during
+// the countdown and launches it when the countdown ends or a human serves
+pub struct ServeSystem {
+    // entity ids
+    ball: uint,
+    left_paddle: uint,
+    right_paddle: uint,
+    countdown: uint
+}
+
+impl ServeSystem {
+    fn hold_ball(&self, entities: &mut [Components], serve: &ServeControl) {
+        let (paddle, offset) = match serve.server {
+            LEFT => (self.left_paddle, 0.25),
+            RIGHT => (self.right_paddle, -0.25)
+        };
+        let paddle_pos = entities[paddle].position.unwrap();
+        let ball = &mut entities[self.ball];
+        let pos = ball.position.get_mut_ref();
+        pos.x = paddle_pos.x + offset;
+        pos.y = paddle_pos.y;
+        let vel = ball.velocity.get_mut_ref();
+        vel.x = 0.0;
+        vel.y = 0.0;
+    }
+
+    fn launch(&self, entities: &mut [Components], resources: &mut Resources) {
+        let direction = match resources.serve.server {
+            LEFT => 1.0,
+            RIGHT => -1.0
+        };
+        let (vx, vy) = resources.ball_physics.serve_velocity(direction, &mut resources.rng);
+        let vel = entities[self.ball].velocity.get_mut_ref();
+        vel.x = vx;
+        vel.y = vy;
+        resources.serve.state = InPlay;
+        resources.serve.launch_requested = false;
+    }
+
+    fn show_countdown(&self, entities: &mut [Components], visible: bool) {
+        entities[self.countdown].sprite.get_mut_ref().visible = visible;
+    }
+}
+
+impl GlobalSystem for ServeSystem {
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, _: &InputSnapshot) -> () {
+        match resources.serve.state {
+            InPlay => {
+                self.show_countdown(entities, false);
+            },
+            OutOfPlay => {
+                self.show_countdown(entities, false);
+                let ball = &mut entities[self.ball];
+                let pos = ball.position.get_mut_ref();
+                pos.x = 2.0;
+                pos.y = 1.5;
+                let vel = ball.velocity.get_mut_ref();
+                vel.x = 0.0;
+                vel.y = 0.0;
+            },
+            Countdown(ticks) => {
+                let countdown_ticks = resources.serve.countdown_ticks;
+                if ticks == countdown_ticks {
+                    match entities[self.countdown].animation {
+                        Some(ref mut anim) => {
+                            anim.frame_ticks = countdown_ticks/anim.frames.len();
+                            anim.restart();
+                        },
+                        None => ()
+                    }
+                }
+                self.show_countdown(entities, true);
+                self.hold_ball(entities, &resources.serve);
+                if ticks > 0 {
+                    resources.serve.state = Countdown(ticks - 1);
+                } else if resources.serve.server_is_human() {
+                    resources.serve.state = WaitingForServe;
+                } else {
+                    self.show_countdown(entities, false);
+                    self.launch(entities, resources);
+                }
+            },
+            WaitingForServe => {
+                self.show_countdown(entities, false);
+                self.hold_ball(entities, &resources.serve);
+                if resources.serve.launch_requested {
+                    self.launch(entities, resources);
+                }
+            }
         }
-        self.paddle.vert_velocity.unwrap().y = 1.5*dir/60.0;
     }
 }
@@ -1,52 +1,372 @@
 // OPENGL ETC STUFF
+extern mod extra;
 extern mod gl;
 extern mod std;
+use extra::time::precise_time_ns;
 use gl::types::*;
-use systems::System;
-use components::Components;
+use console::Command;
+use components::{Components,SpriteTexture,texture_from_char,AabbShape,CircleShape};
+use world::World;
 
 use std::{vec,ptr,str,cast};
 
 pub struct RenderSystem {
+    // can be swapped for freshly compiled ones while running
+    shaders: @mut Shaders,
+    vbo: GLuint,
+    vao: GLuint,
+    char_atlas_tex: GLuint,
+    debug: @mut DebugLayer
+}
+
+pub struct Shaders {
     program: GLuint,
     fs: GLuint,
     vs: GLuint,
-    vbo: GLuint,
-    vao: GLuint,
     position_uniform: GLint,
     scale_uniform: GLint,
     color_uniform: GLint,
     window_uniform: GLint,
     texcoords_uniform: GLint,
-    texenabled_uniform: GLint,
-    char_atlas_tex: GLuint
-}
-
-impl System for RenderSystem {
-    fn process(&self, entity: @Components) -> () {
-        match (entity.position, entity.sprite) {
-            (Some(pos), Some(sprite)) => {
-                // Set uniforms
-                gl::ProgramUniform2f(self.program, self.position_uniform, pos.x as f32, pos.y as f32);
-                gl::ProgramUniform2f(self.program, self.scale_uniform, sprite.x_size as f32, sprite.y_size as f32);
-                //gl::ProgramUniform4fv would probably work for color
-                gl::ProgramUniform4f(self.program, self.color_uniform, sprite.color[0] as f32, sprite.color[1] as f32, sprite.color[2] as f32, sprite.color[3] as f32);
-                match sprite.texture {
-                    Some(tex) => {
-                        gl::BindTexture(gl::TEXTURE_2D, tex.texture);
-                        let (tex_x, tex_y) = tex.texcoords;
-                        let (tex_w, tex_h) = tex.texsize;
-                        gl::ProgramUniform4f(self.program, self.texcoords_uniform, tex_x as f32, tex_y as f32, tex_w as f32, tex_h as f32);
-                        gl::ProgramUniform1f(self.program, self.texenabled_uniform, 1.0 as f32);
-                    },
-                    None => {
-                        gl::ProgramUniform1f(self.program, self.texenabled_uniform, 0.0 as f32);
-                    }
+    texenabled_uniform: GLint
+}
+
+impl Shaders {
+    // compiles main.vs.glsl and main.fs.glsl
+    pub fn load() -> Result<Shaders, ~str> {
+        let vs_src = match read_file("main.vs.glsl") {
+            Some(src) => src,
+            None => return Err(~"can't read main.vs.glsl")
+        };
+        let fs_src = match read_file("main.fs.glsl") {
+            Some(src) => src,
+            None => return Err(~"can't read main.fs.glsl")
+        };
+        let vs = match compile_shader(vs_src, gl::VERTEX_SHADER) {
+            Ok(vs) => vs,
+            Err(e) => return Err(e)
+        };
+        let fs = match compile_shader(fs_src, gl::FRAGMENT_SHADER) {
+            Ok(fs) => fs,
+            Err(e) => {
+                gl::DeleteShader(vs);
+                return Err(e);
+            }
+        };
+        let program = match link_program(vs, fs, "out_color") {
+            Ok(program) => program,
+            Err(e) => {
+                gl::DeleteShader(vs);
+                gl::DeleteShader(fs);
+                return Err(e);
+            }
+        };
+        let uniform = |name: &str| unsafe { name.with_c_str(|ptr| gl::GetUniformLocation(program, ptr)) };
+        Ok(Shaders {
+            program: program,
+            fs: fs,
+            vs: vs,
+            position_uniform: uniform("position"),
+            scale_uniform: uniform("scale"),
+            color_uniform: uniform("color"),
+            window_uniform: uniform("window"),
+            texcoords_uniform: uniform("texcoords"),
+            texenabled_uniform: uniform("texenabled")
+        })
+    }
+
+    fn delete(&self) {
+        gl::DeleteProgram(self.program);
+        gl::DeleteShader(self.fs);
+        gl::DeleteShader(self.vs);
+    }
+}
+
+// None if the file is missing or can't be read, so that a bad reload keeps
+// the old shaders
+fn read_file(path: &str) -> Option<~[u8]> {
+    std::io::io_error::cond.trap(|_| ()).inside(|| {
+        match std::io::fs::File::open_mode(&std::path::Path::new(path), std::io::Open, std::io::Read) {
+            Some(mut file) => Some(file.read_to_end()),
+            None => None
+        }
+    })
+}
+
+// debugging aids drawn over the game, F3 turns them all on or off
+pub struct DebugLayer {
+    // collider outlines, the boxes from Collider::half_extents that the sweeps test
+    hitboxes: bool,
+    velocities: bool,
+    targets: bool,
+    // frames per second and a graph of recent frame times
+    frame_graph: bool,
+    // a list of the entities and their components
+    inspector: bool,
+    // where each bot is sending its paddle, see BotInputSystem
+    bot_targets: ~[(f64, f64)],
+    // in milliseconds, oldest first
+    frame_times: ~[f64],
+    last_frame_ns: u64,
+    // whether a world has been drawn yet this frame, the first one is the
+    // one below any overlays and the one targets and the inspector show
+    world_seen: bool,
+    inspected: Option<~[~str]>
+}
+
+impl DebugLayer {
+    pub fn new() -> DebugLayer {
+        DebugLayer {
+            hitboxes: false,
+            velocities: false,
+            targets: false,
+            frame_graph: false,
+            inspector: false,
+            bot_targets: ~[],
+            frame_times: ~[],
+            last_frame_ns: precise_time_ns(),
+            world_seen: false,
+            inspected: None
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        let on = !(self.hitboxes || self.velocities || self.targets || self.frame_graph || self.inspector);
+        self.hitboxes = on;
+        self.velocities = on;
+        self.targets = on;
+        self.frame_graph = on;
+        self.inspector = on;
+    }
+
+    // called once at the end of every frame
+    pub fn frame_finished(&mut self) {
+        let now = precise_time_ns();
+        self.frame_times.push((now - self.last_frame_ns) as f64/1e6);
+        if self.frame_times.len() > FRAME_GRAPH_FRAMES {
+            self.frame_times.shift();
+        }
+        self.last_frame_ns = now;
+    }
+}
+
+static DEBUG_LINE_WIDTH: f64 = 0.01;
+// velocity arrows show where the entity will be this many ticks later
+static VELOCITY_ARROW_TICKS: f64 = 30.0;
+static FRAME_GRAPH_FRAMES: uint = 120;
+// height of the frame graph per millisecond
+static FRAME_GRAPH_SCALE: f64 = 0.01;
+static FRAME_BUDGET_MS: f64 = 1000.0/60.0;
+static DEBUG_TEXT_WIDTH: f64 = 0.025;
+
+impl RenderSystem {
+    pub fn draw_quad(&self, x: f64, y: f64, x_size: f64, y_size: f64, color: [f64, ..4], texture: Option<SpriteTexture>) {
+        let s = self.shaders;
+        // Set uniforms
+        gl::ProgramUniform2f(s.program, s.position_uniform, x as f32, y as f32);
+        gl::ProgramUniform2f(s.program, s.scale_uniform, x_size as f32, y_size as f32);
+        //gl::ProgramUniform4fv would probably work for color
+        gl::ProgramUniform4f(s.program, s.color_uniform, color[0] as f32, color[1] as f32, color[2] as f32, color[3] as f32);
+        match texture {
+            Some(tex) => {
+                gl::BindTexture(gl::TEXTURE_2D, tex.texture);
+                let (tex_x, tex_y) = tex.texcoords;
+                let (tex_w, tex_h) = tex.texsize;
+                gl::ProgramUniform4f(s.program, s.texcoords_uniform, tex_x as f32, tex_y as f32, tex_w as f32, tex_h as f32);
+                gl::ProgramUniform1f(s.program, s.texenabled_uniform, 1.0 as f32);
+            },
+            None => {
+                gl::ProgramUniform1f(s.program, s.texenabled_uniform, 0.0 as f32);
+            }
+        }
+        // Draw a rect from the 4 vertices
+        gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+    }
+
+    pub fn draw_outline(&self, x: f64, y: f64, x_size: f64, y_size: f64, color: [f64, ..4]) {
+        let w = DEBUG_LINE_WIDTH;
+        self.draw_quad(x, y + y_size/2.0, x_size, w, color, None);
+        self.draw_quad(x, y - y_size/2.0, x_size, w, color, None);
+        self.draw_quad(x - x_size/2.0, y, w, y_size, color, None);
+        self.draw_quad(x + x_size/2.0, y, w, y_size, color, None);
+    }
+
+    // quads can't be rotated, so circles and arrows are drawn as dots
+    pub fn draw_circle(&self, x: f64, y: f64, radius: f64, color: [f64, ..4]) {
+        let dots = 24;
+        for i in range(0, dots) {
+            let angle = i as f64/dots as f64*2.0*std::f64::consts::PI;
+            self.draw_quad(x + radius*angle.cos(), y + radius*angle.sin(), DEBUG_LINE_WIDTH, DEBUG_LINE_WIDTH, color, None);
+        }
+    }
+
+    pub fn draw_arrow(&self, x: f64, y: f64, dx: f64, dy: f64, color: [f64, ..4]) {
+        let length = (dx*dx + dy*dy).sqrt();
+        let dots = (length/(2.0*DEBUG_LINE_WIDTH)).ceil().max(&1.0) as uint;
+        for i in range(0, dots) {
+            let t = i as f64/dots as f64;
+            self.draw_quad(x + t*dx, y + t*dy, DEBUG_LINE_WIDTH, DEBUG_LINE_WIDTH, color, None);
+        }
+        self.draw_quad(x + dx, y + dy, 3.0*DEBUG_LINE_WIDTH, 3.0*DEBUG_LINE_WIDTH, color, None);
+    }
+
+    fn draw_debug(&self, entity: &Components) {
+        let pos = match entity.position {
+            Some(pos) => pos,
+            None => return
+        };
+        match entity.collider {
+            Some(collider) if self.debug.hitboxes => {
+                let (hw, hh) = collider.half_extents();
+                self.draw_outline(pos.x, pos.y, 2.0*hw, 2.0*hh, [0.0, 1.0, 0.0, 1.0]);
+                match collider.shape {
+                    CircleShape(r) => self.draw_circle(pos.x, pos.y, r, [0.0, 0.6, 0.0, 1.0]),
+                    AabbShape(_, _) => ()
                 }
-                // Draw a rect from the 4 vertices
-                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
             },
-            (_, _) => ()
+            _ => ()
+        }
+        match entity.velocity {
+            Some(v) if self.debug.velocities && (v.x != 0.0 || v.y != 0.0) =>
+                self.draw_arrow(pos.x, pos.y, v.x*VELOCITY_ARROW_TICKS, v.y*VELOCITY_ARROW_TICKS, [1.0, 1.0, 0.0, 1.0]),
+            _ => ()
+        }
+    }
+
+    // left aligned, unlike Text entities
+    pub fn draw_label(&self, x: f64, y: f64, char_width: f64, text: &str, color: [f64, ..4]) {
+        for (i, c) in text.chars().enumerate() {
+            let cx = x + (i as f64 + 0.5)*char_width;
+            self.draw_quad(cx, y, char_width, 2.0*char_width, color, Some(texture_from_char(c)));
+        }
+    }
+
+    // the parts of the debug layer that aren't tied to an entity, drawn once
+    // a frame after everything else
+    pub fn draw_debug_overlay(&self) {
+        if self.debug.targets {
+            for &(x, y) in self.debug.bot_targets.iter() {
+                self.draw_quad(x, y, 0.06, DEBUG_LINE_WIDTH, [1.0, 0.0, 1.0, 1.0], None);
+                self.draw_quad(x, y, DEBUG_LINE_WIDTH, 0.06, [1.0, 0.0, 1.0, 1.0], None);
+            }
+        }
+        if self.debug.frame_graph {
+            self.draw_frame_graph();
+        }
+        match self.debug.inspected.take() {
+            Some(lines) => for (i, line) in lines.iter().enumerate() {
+                let y = 2.9 - i as f64*2.4*DEBUG_TEXT_WIDTH;
+                self.draw_label(0.05, y, DEBUG_TEXT_WIDTH, *line, [1.0, 1.0, 0.6, 0.0]);
+            },
+            None => ()
+        }
+        self.debug.bot_targets.clear();
+        self.debug.world_seen = false;
+    }
+
+    // in the bottom right corner, one bar per frame and a line at the
+    // budget for 60 frames per second
+    fn draw_frame_graph(&self) {
+        let times = &self.debug.frame_times;
+        if times.is_empty() {
+            return;
+        }
+        let bar = 0.01;
+        let (right, bottom) = (3.95, 0.3);
+        let left = right - FRAME_GRAPH_FRAMES as f64*bar;
+        let budget = FRAME_BUDGET_MS*FRAME_GRAPH_SCALE;
+        self.draw_quad((left + right)/2.0, bottom + budget, right - left, DEBUG_LINE_WIDTH/2.0, [1.0, 1.0, 1.0, 0.5], None);
+        let first = FRAME_GRAPH_FRAMES - times.len();
+        for (i, &ms) in times.iter().enumerate() {
+            let height = ms*FRAME_GRAPH_SCALE;
+            let color = if ms > FRAME_BUDGET_MS*1.1 { [1.0, 0.2, 0.2, 1.0] } else { [0.2, 1.0, 0.2, 1.0] };
+            self.draw_quad(left + ((first + i) as f64 + 0.5)*bar, bottom + height/2.0, bar, height, color, None);
+        }
+        let average = times.iter().fold(0.0, |a, &ms| a + ms)/times.len() as f64;
+        let text = format!("{:.0f} FPS {:.1f} MS", 1000.0/average, average);
+        self.draw_label(left, bottom - 0.05, DEBUG_TEXT_WIDTH, text, [1.0, 1.0, 1.0, 0.0]);
+    }
+}
+
+// one line of the inspector
+fn describe_entity(i: uint, entity: &Components) -> ~str {
+    let mut parts = ~[format!("{:2u}", i)];
+    match entity.position {
+        Some(pos) => parts.push(format!("POS {:.2f},{:.2f}", pos.x, pos.y)),
+        None => ()
+    }
+    match entity.velocity {
+        Some(v) => parts.push(format!("VEL {:.3f},{:.3f}", v.x, v.y)),
+        None => ()
+    }
+    match entity.acceleration {
+        Some(a) => parts.push(format!("ACC {:.4f},{:.4f}", a.x, a.y)),
+        None => ()
+    }
+    match entity.collider {
+        Some(collider) => parts.push(match collider.shape {
+            AabbShape(w, h) => format!("AABB {:.2f}X{:.2f} L{}", w, h, collider.layer),
+            CircleShape(r) => format!("CIRCLE {:.2f} L{}", r, collider.layer)
+        }),
+        None => ()
+    }
+    match entity.bounds {
+        Some(b) => parts.push(format!("BOUNDS {:.2f}-{:.2f}", b.min_y, b.max_y)),
+        None => ()
+    }
+    match entity.sprite {
+        Some(sprite) => parts.push(format!("SPRITE {:.2f}X{:.2f}{:s}", sprite.x_size, sprite.y_size, if sprite.visible { "" } else { " HIDDEN" })),
+        None => ()
+    }
+    match entity.animation {
+        Some(_) => parts.push(~"ANIM"),
+        None => ()
+    }
+    match entity.text {
+        Some(ref text) => parts.push(format!("TEXT \"{:s}\"", text.text)),
+        None => ()
+    }
+    parts.connect(" ")
+}
+
+impl RenderSystem {
+    fn draw_entity(&self, entity: &Components) {
+        match (entity.position, entity.sprite, &entity.text) {
+            // text is drawn one glyph per sprite width, centered on the position
+            (Some(pos), Some(sprite), &Some(ref text)) if sprite.visible => {
+                let (sx, sy) = sprite.scale;
+                let (w, h) = (sprite.x_size*sx, sprite.y_size*sy);
+                let len = text.text.char_len() as f64;
+                for (i, c) in text.text.chars().enumerate() {
+                    let x = pos.x + (i as f64 - (len - 1.0)/2.0)*w;
+                    self.draw_quad(x, pos.y, w, h, sprite.color, Some(texture_from_char(c)));
+                }
+            },
+            (Some(pos), Some(sprite), &None) if sprite.visible => {
+                let (sx, sy) = sprite.scale;
+                self.draw_quad(pos.x, pos.y, sprite.x_size*sx, sprite.y_size*sy, sprite.color, sprite.texture);
+            },
+            (_, _, _) => ()
+        }
+        self.draw_debug(entity);
+    }
+
+    pub fn render(&self, world: &World) {
+        for entity in world.entities.iter() {
+            self.draw_entity(entity);
+        }
+        if self.debug.world_seen {
+            return;
+        }
+        self.debug.world_seen = true;
+        for target in world.resources.bot_targets.iter() {
+            match *target {
+                Some(target) => self.debug.bot_targets.push(target),
+                None => ()
+            }
+        }
+        if self.debug.inspector {
+            self.debug.inspected = Some(world.entities.iter().enumerate().map(|(i, e)| describe_entity(i, e)).collect());
         }
     }
 }
@@ -59,7 +379,7 @@ static VERTEX_DATA: [GLfloat, ..8] = [
      0.5, -0.5
 ];
 
-fn compile_shader(src: &[u8], ty: GLenum) -> GLuint {
+fn compile_shader(src: &[u8], ty: GLenum) -> Result<GLuint, ~str> {
     let shader = gl::CreateShader(ty);
     unsafe {
         // Attempt to compile the shader
@@ -78,13 +398,14 @@ fn compile_shader(src: &[u8], ty: GLenum) -> GLuint {
             gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
             let mut buf = vec::from_elem(len as uint - 1, 0u8);     // subtract 1 to skip the trailing null character
             gl::GetShaderInfoLog(shader, len, ptr::mut_null(), buf.as_mut_ptr() as *mut GLchar);
-            fail!(str::raw::from_utf8(buf).to_owned());
+            gl::DeleteShader(shader);
+            return Err(str::raw::from_utf8(buf).to_owned());
         }
     }
-    shader
+    Ok(shader)
 }
 
-fn link_program(vs: GLuint, fs: GLuint, out_color: &str) -> GLuint {
+fn link_program(vs: GLuint, fs: GLuint, out_color: &str) -> Result<GLuint, ~str> {
     let program = gl::CreateProgram();
     gl::AttachShader(program, vs);
     gl::AttachShader(program, fs);
@@ -104,38 +425,35 @@ fn link_program(vs: GLuint, fs: GLuint, out_color: &str) -> GLuint {
             gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
             let mut buf = vec::from_elem(len as uint - 1, 0u8);     // subtract 1 to skip the trailing null character
             gl::GetProgramInfoLog(program, len, ptr::mut_null(), buf.as_mut_ptr() as *mut GLchar);
-            fail!(str::raw::from_utf8(buf).to_owned());
+            gl::DeleteProgram(program);
+            return Err(str::raw::from_utf8(buf).to_owned());
         }
     }
-    program
+    Ok(program)
+}
+
+// points the program's vertex attribute at the quad in the bound vertex buffer
+fn bind_vertex_attribute(program: GLuint) {
+    unsafe {
+        let vert_attr = "vertex".with_c_str(|ptr| gl::GetAttribLocation(program, ptr));
+        gl::EnableVertexAttribArray(vert_attr as GLuint);
+        gl::VertexAttribPointer(vert_attr as GLuint, 2, gl::FLOAT,
+                                gl::FALSE as GLboolean, 0, ptr::null());
+    }
 }
 
 impl RenderSystem {
     pub fn new() -> RenderSystem {
         // Create GLSL shaders
-        let vs_src = std::io::fs::File::open_mode(&std::path::Path::new("main.vs.glsl"), std::io::Open, std::io::Read).unwrap().read_to_end();
-        let vs = compile_shader(vs_src, gl::VERTEX_SHADER);
-        let fs_src = std::io::fs::File::open_mode(&std::path::Path::new("main.fs.glsl"), std::io::Open, std::io::Read).unwrap().read_to_end();
-        let fs = compile_shader(fs_src, gl::FRAGMENT_SHADER);
-        let program = link_program(vs, fs, "out_color");
+        let shaders = match Shaders::load() {
+            Ok(shaders) => shaders,
+            Err(e) => fail!(e)
+        };
 
         let mut vao = 0;
         let mut vbo = 0;
 
-        let position_uniform: GLint;
-        let scale_uniform: GLint;
-        let color_uniform: GLint;
-        let window_uniform: GLint;
-        let texcoords_uniform: GLint;
-        let texenabled_uniform: GLint;
-
         unsafe {
-            position_uniform = "position".with_c_str(|ptr| gl::GetUniformLocation(program, ptr));
-            scale_uniform = "scale".with_c_str(|ptr| gl::GetUniformLocation(program, ptr));
-            color_uniform = "color".with_c_str(|ptr| gl::GetUniformLocation(program, ptr));
-            window_uniform = "window".with_c_str(|ptr| gl::GetUniformLocation(program, ptr));
-            texcoords_uniform = "texcoords".with_c_str(|ptr| gl::GetUniformLocation(program, ptr));
-            texenabled_uniform = "texenabled".with_c_str(|ptr| gl::GetUniformLocation(program, ptr));
             // Create Vertex Array Object
             gl::GenVertexArrays(1, &mut vao);
             gl::BindVertexArray(vao);
@@ -148,15 +466,11 @@ impl RenderSystem {
                            cast::transmute(&VERTEX_DATA[0]),
                            gl::STATIC_DRAW);
 
-            // Use shader program
-            gl::UseProgram(program);
-
-            // Specify the layout of the vertex data
-            let vert_attr = "vertex".with_c_str(|ptr| gl::GetAttribLocation(program, ptr));
-            gl::EnableVertexAttribArray(vert_attr as GLuint);
-            gl::VertexAttribPointer(vert_attr as GLuint, 2, gl::FLOAT,
-                                    gl::FALSE as GLboolean, 0, ptr::null());
         }
+        // Use shader program
+        gl::UseProgram(shaders.program);
+        // Specify the layout of the vertex data
+        bind_vertex_attribute(shaders.program);
         //enable alpha blending
         gl::Enable(gl::BLEND);
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
@@ -174,28 +488,35 @@ impl RenderSystem {
         }
 
         RenderSystem {
-            program: program,
-            fs: fs,
-            vs: vs,
+            shaders: @mut shaders,
             vbo: vbo,
             vao: vao,
-            position_uniform: position_uniform,
-            scale_uniform: scale_uniform,
-            color_uniform: color_uniform,
-            window_uniform: window_uniform,
-            texcoords_uniform: texcoords_uniform,
-            texenabled_uniform: texenabled_uniform,
-            char_atlas_tex: char_atlas_tex
+            char_atlas_tex: char_atlas_tex,
+            debug: @mut DebugLayer::new()
         }
     }
+
+    // compiles the shaders again from their files, the old ones are kept
+    // if the new ones don't compile
+    pub fn reload_shaders(&self) -> Result<(), ~str> {
+        let shaders = match Shaders::load() {
+            Ok(shaders) => shaders,
+            Err(e) => return Err(e)
+        };
+        self.shaders.delete();
+        gl::BindVertexArray(self.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        gl::UseProgram(shaders.program);
+        bind_vertex_attribute(shaders.program);
+        *self.shaders = shaders;
+        Ok(())
+    }
 }
 
 impl Drop for RenderSystem {
     fn drop(&mut self) {
         // Cleanup
-        gl::DeleteProgram(self.program);
-        gl::DeleteShader(self.fs);
-        gl::DeleteShader(self.vs);
+        self.shaders.delete();
         unsafe {
             gl::DeleteBuffers(1, &self.vbo);
             gl::DeleteVertexArrays(1, &self.vao);
@@ -203,3 +524,17 @@ impl Drop for RenderSystem {
         }
     }
 }
+
+// `reload shaders` in the console
+pub struct ReloadCommand {
+    rs: @RenderSystem
+}
+
+impl Command for ReloadCommand {
+    fn run(&self, args: &[&str]) -> Result<~str, ~str> {
+        match args {
+            ["shaders"] => self.rs.reload_shaders().map(|_| ~"shaders reloaded"),
+            _ => Err(~"only shaders can be reloaded")
+        }
+    }
+}
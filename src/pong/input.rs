@@ -0,0 +1,194 @@
+// INPUT ACTIONS
+// what the game reads of the players' keys, pads and mouse. Reading them off
+// a window and the key bindings are up to the binary, see bindings.rs and
+// gamepad.rs
+extern mod std;
+
+#[deriving(Eq, Clone)]
+pub enum Action {
+    PaddleUp,
+    PaddleDown,
+    Serve,
+    Pause,
+    Quit
+}
+
+pub static ACTIONS: [Action, ..5] = [PaddleUp, PaddleDown, Serve, Pause, Quit];
+
+impl Action {
+    // name used in the bindings file
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PaddleUp => "paddle_up",
+            PaddleDown => "paddle_down",
+            Serve => "serve",
+            Pause => "pause",
+            Quit => "quit"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().find(|a| a.name() == name).map(|a| *a)
+    }
+
+    pub fn is_per_player(&self) -> bool {
+        PLAYER_ACTIONS.contains(self)
+    }
+
+    // name shown in menus
+    pub fn label(&self) -> &'static str {
+        match *self {
+            PaddleUp => "PADDLE UP",
+            PaddleDown => "PADDLE DOWN",
+            Serve => "SERVE",
+            Pause => "PAUSE",
+            Quit => "QUIT"
+        }
+    }
+}
+
+// number of players that can share the keyboard, player 0 controls the left
+// paddle and player 1 the right one
+pub static PLAYERS: uint = 2;
+
+// actions that each player has their own keys for, the rest are shared and
+// bound for player 0 only
+pub static PLAYER_ACTIONS: [Action, ..3] = [PaddleUp, PaddleDown, Serve];
+
+// top speed of a paddle driven by a player, in arena units per tick
+pub static PADDLE_SPEED: f64 = 1.5/60.0;
+
+// the keyboard, gamepads and mouse as of the start of a frame, so that the
+// game itself doesn't need a window, eg. when run headless for training bots
+pub struct InputSnapshot {
+    // (player, action) pairs with a bound key held down
+    keys: ~[(uint, Action)],
+    // (player, action) pairs with a gamepad button held down
+    buttons: ~[(uint, Action)],
+    // paddle velocity from each player's gamepad stick, 0 without a pad
+    sticks: [f64, ..2],
+    // in window coordinates
+    cursor: (f64, f64),
+    window_size: (f64, f64),
+    // left button
+    mouse_down: bool
+}
+
+impl InputSnapshot {
+    // nothing held, for headless runs
+    pub fn none() -> InputSnapshot {
+        InputSnapshot {
+            keys: ~[],
+            buttons: ~[],
+            sticks: [0.0, 0.0],
+            cursor: (0.0, 0.0),
+            window_size: (0.0, 0.0),
+            mouse_down: false
+        }
+    }
+
+    pub fn key_held(&self, player: uint, action: Action) -> bool {
+        self.keys.contains(&(player, action))
+    }
+
+    pub fn button_held(&self, player: uint, action: Action) -> bool {
+        self.buttons.contains(&(player, action))
+    }
+}
+
+// size of the arena in world units, as in main.vs.glsl
+static WORLDSPACE_SIZE: (f64, f64) = (4.0, 3.0);
+
+// inverse of the world to screen transform in main.vs.glsl, maps a point in
+// window coordinates (origin at the top left, y down) to world coordinates
+pub fn screen_to_world(point: (f64, f64), window_size: (f64, f64)) -> (f64, f64) {
+    let (px, py) = point;
+    let (w, h) = window_size;
+    let (world_w, world_h) = WORLDSPACE_SIZE;
+    let origin_x = -(0.9*h*world_w/world_h)/w;
+    let origin_y = -0.9;
+    let (scale_x, scale_y) = (-2.0*origin_x/world_w, -2.0*origin_y/world_h);
+    // window to normalized device coordinates
+    let (ndc_x, ndc_y) = (2.0*px/w - 1.0, 1.0 - 2.0*py/h);
+    ((ndc_x - origin_x)/scale_x, (ndc_y - origin_y)/scale_y)
+}
+
+// something a player can move their paddle with
+pub trait InputSource {
+    // in arena units per tick, positive is up, for a paddle at `paddle_y`
+    fn paddle_velocity(&self, input: &InputSnapshot, paddle_y: f64) -> f64;
+
+    fn is_held(&self, input: &InputSnapshot, action: Action) -> bool;
+}
+
+// when a player uses several sources at once the fastest one wins
+pub fn combined_velocity(sources: &[~InputSource], input: &InputSnapshot, paddle_y: f64) -> f64 {
+    let mut velocity = 0.0;
+    for source in sources.iter() {
+        let v = source.paddle_velocity(input, paddle_y);
+        if std::num::abs(v) > std::num::abs(velocity) {
+            velocity = v;
+        }
+    }
+    velocity
+}
+
+pub struct KeyboardSource {
+    player: uint
+}
+
+impl InputSource for KeyboardSource {
+    fn paddle_velocity(&self, input: &InputSnapshot, _: f64) -> f64 {
+        let mut dir = 0.0;
+        if input.key_held(self.player, PaddleUp) {
+            dir += 1.0;
+        }
+        if input.key_held(self.player, PaddleDown) {
+            dir -= 1.0;
+        }
+        dir*PADDLE_SPEED
+    }
+
+    fn is_held(&self, input: &InputSnapshot, action: Action) -> bool {
+        input.key_held(self.player, action)
+    }
+}
+
+// a disconnected pad just reads as idle
+pub struct GamepadSource {
+    player: uint
+}
+
+impl InputSource for GamepadSource {
+    fn paddle_velocity(&self, input: &InputSnapshot, _: f64) -> f64 {
+        input.sticks[self.player]
+    }
+
+    fn is_held(&self, input: &InputSnapshot, action: Action) -> bool {
+        input.button_held(self.player, action)
+    }
+}
+
+// the paddle follows the mouse cursor's height, at most `max_speed` arena units
+// per tick so that it can't teleport, and the left button serves
+pub struct MouseSource {
+    max_speed: f64
+}
+
+impl InputSource for MouseSource {
+    fn paddle_velocity(&self, input: &InputSnapshot, paddle_y: f64) -> f64 {
+        let (w, h) = input.window_size;
+        if w <= 0.0 || h <= 0.0 {
+            return 0.0;
+        }
+        let (_, target) = screen_to_world(input.cursor, input.window_size);
+        (target - paddle_y).max(&-self.max_speed).min(&self.max_speed)
+    }
+
+    fn is_held(&self, input: &InputSnapshot, action: Action) -> bool {
+        match action {
+            Serve => input.mouse_down,
+            _ => false
+        }
+    }
+}
@@ -0,0 +1,25 @@
+// the game without its window, main loop and menus, for driving matches from
+// other programs, see env::PongEnv. Nothing in here links glfw or gl, the
+// window, key bindings and drawing are only part of the pong binary
+#[crate_type = "lib"];
+#[feature(globs)];
+#[feature(macro_rules)];
+
+pub mod ai;
+pub mod batch;
+pub mod broadcast;
+pub mod client;
+pub mod collision;
+pub mod components;
+pub mod entities;
+pub mod env;
+pub mod extbot;
+pub mod game;
+pub mod globalsystems;
+pub mod input;
+pub mod netplay;
+pub mod resources;
+pub mod server;
+pub mod systems;
+pub mod tournament;
+pub mod world;
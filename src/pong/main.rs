@@ -19,47 +19,30 @@ extern mod std;
 use gl::types::*;
 
 use callbacks::*;
-use entities::*;
-use components::*;
-use globalsystems::*;
-use systems::*;
+use entities::{LEFT,RIGHT};
+use extbot::BotLink;
 use graphics::RenderSystem;
-use world::World;
+use bindings::capture_input;
+use broadcast::{BroadcastConfig,Broadcaster,Recording};
+use client::ClientConfig;
+use console::{Command,Console,CONSOLE_SCRIPT};
+use graphics::ReloadCommand;
+use netplay::{NetplayConfig,NetConditions,Host,Join};
+use resources::GameMode;
+use settings::Settings;
+use states::*;
 
 pub fn main() {
+    // `pong tournament ...` plays bots against each other without a window
+    let args = std::os::args();
+    if args.len() > 1 && args[1] == ~"tournament" {
+        ::tournament::run(args.slice_from(2));
+        return;
+    }
+
     glfw::set_error_callback(~ErrorContext);
 
     do glfw::start {
-        // initialize game world
-        let left_paddle: @Components = new_paddle(LEFT);
-        let right_paddle: @Components = new_paddle(RIGHT);
-        let ball: @Components = new_ball();
-        let left_score_counter: @Components = new_score_counter(LEFT);
-        let right_score_counter: @Components = new_score_counter(RIGHT);
-        let background: @Components = new_background();
-        let background_2: @Components = new_background_2();
-        let ms = @MovementSystem;
-        let es = @EdgeCollisionSystem;
-        let (left_score_port, left_score_chan): (Port<uint>, Chan<uint>) = std::comm::Chan::new();
-        let (right_score_port, right_score_chan): (Port<uint>, Chan<uint>) = std::comm::Chan::new();
-        let ss = @ScoreCollisionSystem { left_chan: left_score_chan, right_chan: right_score_chan };
-        let lps = @PaddleCollisionSystem{ paddle: left_paddle };
-        let rps = @PaddleCollisionSystem{ paddle: right_paddle };
-
-        let mut world: World = World::new();
-        world.entities.push(background);
-        world.entities.push(background_2);
-        world.entities.push(left_score_counter);
-        world.entities.push(right_score_counter);
-        world.entities.push(left_paddle);
-        world.entities.push(right_paddle);
-        world.entities.push(ball);
-        world.systems.push(ms as @System);
-        world.systems.push(es as @System);
-        world.systems.push(ss as @System);
-        world.systems.push(lps as @System);
-        world.systems.push(rps as @System);
-
         // Choose a GL profile that is compatible with OS X 10.7+
         glfw::window_hint::context_version(3, 2);
         glfw::window_hint::opengl_profile(glfw::OpenGlCoreProfile);
@@ -68,7 +51,10 @@ pub fn main() {
         let mut window_width = 800;
         let mut window_height = 480;
         let window = glfw::Window::create(window_width, window_height, "Pong", glfw::Windowed).expect("Failed to create GLFW window.");;
-        window.set_key_callback(~KeyContext);
+        let (key_port, key_chan): (Port<(glfw::Key, bool)>, Chan<(glfw::Key, bool)>) = std::comm::Chan::new();
+        window.set_key_callback(~KeyContext { chan: key_chan });
+        let (char_port, char_chan): (Port<char>, Chan<char>) = std::comm::Chan::new();
+        window.set_char_callback(~CharContext { chan: char_chan });
         window.make_context_current();
 
         // Load the OpenGL function pointers
@@ -79,21 +65,134 @@ pub fn main() {
         let (fb_size_port, fb_size_chan): (Port<(u32,u32)>, Chan<(u32,u32)>) = std::comm::Chan::new();
         window.set_framebuffer_size_callback(~FramebufferSizeContext { chan: fb_size_chan });
 
-        world.systems.push(rs as @System);
-
-        let kbs = @mut KeyboardInputSystem { paddle: left_paddle };
-        world.global_systems.push(kbs as @mut GlobalSystem);
-
-        let bis = @mut BotInputSystem { paddle: right_paddle, ball: ball };
-        world.global_systems.push(bis as @mut GlobalSystem);
-
-        // score update systems need to be mutable as they maintain the score within
-        let lsus = @mut ScoreUpdateSystem { paddle: left_paddle, counter: left_score_counter, score: 0, port: left_score_port };
-        world.global_systems.push(lsus as @mut GlobalSystem);
-        let rsus = @mut ScoreUpdateSystem { paddle: right_paddle, counter: right_score_counter, score: 0, port: right_score_port };
-        world.global_systems.push(rsus as @mut GlobalSystem);
+        let settings = @mut Settings::new();
+        let mut console = Console::new();
+        console.register("reload", "shaders", @ReloadCommand { rs: rs } as @Command);
+        console.register("timescale", "[SCALE]", @TimescaleCommand { settings: settings } as @Command);
+        // `--mode hvb|hvh|bvb` skips the title screen
+        // `--bot-log` prints the decisions of the aimer bot
+        // `--left-bot SPEC` and `--right-bot SPEC` hand a bot's paddle to an
+        // external bot, see extbot::BotLink::from_spec
+        // `--bot-timeout MS` is how long external bots get per tick
+        // `--host PORT` or `--join ADDRESS:PORT` play against another player
+        // over the network, `--local-port PORT` is where a joining player
+        // listens, `--input-delay TICKS` delays local inputs and `--latency MS`
+        // and `--loss FRACTION` simulate a bad network, see netplay.rs
+        // `--connect ADDRESS:PORT` plays on a dedicated server, or watches
+        // with `--spectate`, see server.rs
+        // `--broadcast ADDRESS:PORT` lets viewers watch every match, delayed
+        // by `--broadcast-delay SECONDS`, and `--record FILE` writes the
+        // matches to a file, see broadcast.rs. `--watch ADDRESS:PORT` shows
+        // a broadcast. `--replay FILE` plays back a recording.
+        let args = std::os::args();
+        let mut broadcast = BroadcastConfig::new();
+        let mut replay = None;
+        let mut watch = None;
+        let spectate = args.contains(&~"--spectate");
+        settings.bot_log = args.contains(&~"--bot-log");
+        let mut server = None;
+        let mut mode = None;
+        let mut netplay = None;
+        let mut local_port = None;
+        let mut conditions = NetConditions::new();
+        let mut input_delay = 2;
+        for (i, arg) in args.iter().enumerate() {
+            if i + 1 >= args.len() {
+                break;
+            }
+            let value = args[i + 1].as_slice();
+            match arg.as_slice() {
+                "--mode" => {
+                    mode = GameMode::from_name(value);
+                    if mode.is_none() {
+                        println!("unknown mode {:s}, expected hvb, hvh or bvb", value);
+                    }
+                },
+                "--left-bot" => settings.external_bots[LEFT.index()] = Some(BotLink::from_spec(value)),
+                "--right-bot" => settings.external_bots[RIGHT.index()] = Some(BotLink::from_spec(value)),
+                "--bot-timeout" => match from_str::<u64>(value) {
+                    Some(ms) => settings.external_timeout_ms = ms,
+                    None => println!("bad bot timeout {:s}, expected milliseconds", value)
+                },
+                "--host" => match from_str::<u16>(value) {
+                    Some(port) => {
+                        netplay = Some(Host);
+                        local_port = Some(port);
+                    },
+                    None => println!("bad port {:s}", value)
+                },
+                "--join" => match from_str(value) {
+                    Some(addr) => netplay = Some(Join(addr)),
+                    None => println!("bad address {:s}, expected ADDRESS:PORT", value)
+                },
+                "--connect" => match from_str(value) {
+                    Some(addr) => server = Some(addr),
+                    None => println!("bad address {:s}, expected ADDRESS:PORT", value)
+                },
+                "--broadcast" => broadcast.address = Some(value.to_owned()),
+                "--broadcast-delay" => match from_str::<f64>(value) {
+                    Some(secs) => broadcast.delay_secs = secs,
+                    None => println!("bad broadcast delay {:s}, expected seconds", value)
+                },
+                "--record" => broadcast.record = Some(value.to_owned()),
+                "--watch" => watch = Some(value.to_owned()),
+                "--replay" => replay = Some(value.to_owned()),
+                "--local-port" => match from_str::<u16>(value) {
+                    Some(port) => local_port = Some(port),
+                    None => println!("bad port {:s}", value)
+                },
+                "--input-delay" => match from_str::<uint>(value) {
+                    Some(ticks) => input_delay = ticks,
+                    None => println!("bad input delay {:s}, expected ticks", value)
+                },
+                "--latency" => match from_str::<u64>(value) {
+                    Some(ms) => conditions.latency_ms = ms,
+                    None => println!("bad latency {:s}, expected milliseconds", value)
+                },
+                "--loss" => match from_str::<f64>(value) {
+                    Some(loss) => conditions.loss = loss,
+                    None => println!("bad loss {:s}, expected a fraction", value)
+                },
+                _ => ()
+            }
+        }
+        if broadcast.is_enabled() {
+            settings.broadcaster = Some(@mut Broadcaster::new(&broadcast));
+        }
+        let recording = replay.and_then(|path| Recording::load(path.as_slice()));
+        let mut states = match (recording, watch, server, netplay, mode) {
+            (Some(recording), _, _, _, _) => StateStack::new(@mut ReplayState::new(rs, settings, recording) as @mut GameState),
+            (None, Some(address), _, _, _) => StateStack::new(@mut WatchState::new(rs, settings, address) as @mut GameState),
+            (None, None, Some(addr), _, _) => {
+                let config = ClientConfig {
+                    server: addr,
+                    local_port: local_port.unwrap_or(7001),
+                    conditions: conditions,
+                    spectate: spectate
+                };
+                StateStack::new(@mut ClientState::new(rs, settings, config) as @mut GameState)
+            },
+            (None, None, None, Some(role), _) => {
+                let config = NetplayConfig {
+                    role: role,
+                    local_port: local_port.unwrap_or(7001),
+                    conditions: conditions,
+                    input_delay: input_delay
+                };
+                StateStack::new(@mut LobbyState::new(rs, settings, config) as @mut GameState)
+            },
+            (None, None, None, None, Some(m)) => {
+                settings.mode = m;
+                StateStack::new(@mut PlayingState::new(rs, settings) as @mut GameState)
+            },
+            (None, None, None, None, None) => StateStack::new(@mut TitleState::new(rs, settings) as @mut GameState)
+        };
+        // lines for the match wait until one starts, see Console::run_waiting
+        if std::path::Path::new(CONSOLE_SCRIPT).exists() {
+            console.run_script(CONSOLE_SCRIPT, &mut states);
+        }
 
-        while !window.should_close() {
+        while !window.should_close() && !states.is_empty() {
             // Poll events
             glfw::poll_events();
 
@@ -107,18 +206,64 @@ pub fn main() {
                 }
             }
 
+            loop {
+                match key_port.try_recv() {
+                    // F3 works everywhere, see graphics::DebugLayer
+                    Some((glfw::KeyF3, false)) => rs.debug.toggle(),
+                    Some((glfw::KeyF3, true)) => (),
+                    Some((key, repeat)) => if !console.key_pressed(key, repeat, &mut states) {
+                        if repeat {
+                            states.key_repeated(key);
+                        } else {
+                            states.key_pressed(key);
+                        }
+                    },
+                    None => break
+                }
+            }
+
+            loop {
+                match char_port.try_recv() {
+                    Some(c) => console.char_typed(c),
+                    None => break
+                }
+            }
+
+            // gamepads are polled rather than reported through callbacks, this
+            // also picks up pads that were plugged in or out
+            let gamepad_actions = settings.gamepads.poll();
+            for &(_, action) in gamepad_actions.iter() {
+                states.action_pressed(action);
+            }
+
+            // process the topmost game state, keys typed into the console
+            // don't move paddles
+            let mut input = capture_input(&window, &*settings.bindings, &*settings.gamepads);
+            if console.open {
+                input.keys = ~[];
+                input.mouse_down = false;
+            }
+            states.update(&input);
+            console.run_waiting(&mut states);
+
+            match settings.broadcaster {
+                Some(broadcaster) => broadcaster.release(),
+                None => ()
+            }
+
             gl::Viewport(0,0, window_width as GLint, window_height as GLint);
-            gl::ProgramUniform2f(rs.program, rs.window_uniform, window_width as f32, window_height as f32);
+            gl::ProgramUniform2f(rs.shaders.program, rs.shaders.window_uniform, window_width as f32, window_height as f32);
             // Clear the screen
             gl::ClearColor(0.8, 0.8, 0.8, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
 
-            // process game world
-            world.process(&window);
+            states.render();
+            rs.draw_debug_overlay();
+            console.render(rs);
+            rs.debug.frame_finished();
 
             // Swap buffers
             window.swap_buffers();
         }
     }
 }
-
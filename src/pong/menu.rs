@@ -0,0 +1,76 @@
+// MENUS
+extern mod glfw;
+use entities::new_text;
+use globalsystems::pop;
+use world::World;
+
+static NORMAL_COLOR: [f64, ..4] = [1.0, 1.0, 1.0, 0.0];
+static SELECTED_COLOR: [f64, ..4] = [1.0, 0.9, 0.2, 0.0];
+
+pub enum MenuAction {
+    MenuNone,
+    MenuSelect(uint),
+    // left/right on an item, for changing a setting
+    MenuAdjust(uint, int),
+    MenuBack
+}
+
+// a vertical list of text entities navigated with the keyboard
+pub struct Menu {
+    // entities in the world the menu was made in
+    items: ~[uint],
+    selected: uint
+}
+
+impl Menu {
+    // lays the items out downwards from (x, y), long menus are packed
+    // tighter so that they still fit above the bottom edge, with smaller
+    // text so that the lines don't overlap
+    pub fn new(world: &mut World, labels: &[~str], x: f64, y: f64) -> Menu {
+        let spacing = (y/(labels.len() as f64)).min(&0.25);
+        // text is twice as high as it is wide
+        let char_width = (spacing/2.0).min(&0.1);
+        let mut items = ~[];
+        for (i, label) in labels.iter().enumerate() {
+            items.push(world.add(new_text(x, y - spacing*(i as f64), char_width, *label, NORMAL_COLOR)));
+        }
+        let menu = Menu { items: items, selected: 0 };
+        menu.highlight(world);
+        menu
+    }
+
+    pub fn set_label(&self, world: &mut World, i: uint, label: ~str) {
+        world.entities[self.items[i]].text.get_mut_ref().text = label;
+    }
+
+    fn highlight(&self, world: &mut World) {
+        for (i, &item) in self.items.iter().enumerate() {
+            world.entities[item].sprite.get_mut_ref().color = if i == self.selected { SELECTED_COLOR } else { NORMAL_COLOR };
+        }
+    }
+
+    fn move_selection(&mut self, world: &mut World, delta: int) {
+        let len = self.items.len() as int;
+        self.selected = (((self.selected as int + delta) % len + len) % len) as uint;
+        self.highlight(world);
+        pop(&mut world.entities[self.items[self.selected]], 1.2);
+    }
+
+    pub fn key_pressed(&mut self, world: &mut World, key: glfw::Key) -> MenuAction {
+        match key {
+            glfw::KeyUp => {
+                self.move_selection(world, -1);
+                MenuNone
+            },
+            glfw::KeyDown => {
+                self.move_selection(world, 1);
+                MenuNone
+            },
+            glfw::KeyLeft => MenuAdjust(self.selected, -1),
+            glfw::KeyRight => MenuAdjust(self.selected, 1),
+            glfw::KeyEnter | glfw::KeySpace => MenuSelect(self.selected),
+            glfw::KeyEscape | glfw::KeyBackspace => MenuBack,
+            _ => MenuNone
+        }
+    }
+}
@@ -0,0 +1,877 @@
+// NETPLAY
+// two players on different machines over UDP. Both peers run the whole match
+// and only inputs are exchanged. Local inputs are delayed by a few ticks to
+// give them time to reach the other side, and when a remote input is still
+// late the match goes on with a guess, the last input that did arrive. Once
+// the real input is in, the match is rolled back to a saved state and the
+// ticks since are run again. Peers compare checksums of the state every so
+// often to notice when their matches have drifted apart.
+//
+// Packets are single lines of text:
+//   HELLO <port>                       joining peer, the port it listens on
+//   WELCOME <seed> <rules>             host, the match to play
+//   INPUT <first tick> <inputs> <ack> <check tick> <checksum>
+// where inputs has one digit per tick and is resent until the peer has
+// acknowledged receiving every input before `ack`.
+extern mod extra;
+extern mod std;
+use extra::time::precise_time_ns;
+use std::io::io_error;
+use std::io::net::ip::SocketAddr;
+use std::io::net::udp::UdpSocket;
+use std::rand::{Rng,XorShiftRng};
+use components::{Components,Position,Velocity};
+use entities::{PaddleSide,LEFT,RIGHT};
+use game::{MatchWorld,new_match_world,seeded_rng};
+use globalsystems::GlobalSystem;
+use input::{InputSnapshot,PADDLE_SPEED};
+use resources::*;
+
+// how many ticks the match may run ahead of the last known remote input
+pub static MAX_ROLLBACK: uint = 15;
+// states kept for rolling back to
+static SNAPSHOTS: uint = MAX_ROLLBACK + 1;
+// ticks between state checksums
+static CHECK_INTERVAL: uint = 30;
+// checksums kept for comparing
+static CHECKSUMS: uint = 16;
+// inputs per packet, newer ones wait until older ones are acknowledged
+static MAX_PACKET_INPUTS: uint = 64;
+// how often the joining peer says hello, in ticks
+static HELLO_INTERVAL: uint = 30;
+// a peer that hasn't been heard from for this long is gone
+static PEER_TIMEOUT_NS: u64 = 5000000000;
+
+// one player's input for one tick. Analog input is reduced to its direction,
+// so that it fits into a digit.
+#[deriving(Eq, Clone)]
+pub struct NetInput {
+    // -1, 0 or 1, up being positive
+    dir: int,
+    serve: bool
+}
+
+impl NetInput {
+    pub fn idle() -> NetInput {
+        NetInput { dir: 0, serve: false }
+    }
+
+    pub fn from_velocity(velocity: f64, serve: bool) -> NetInput {
+        let dir = if velocity > 0.0 { 1 } else if velocity < 0.0 { -1 } else { 0 };
+        NetInput { dir: dir, serve: serve }
+    }
+
+    pub fn encode(&self) -> char {
+        let digit = (self.dir + 1) as uint + if self.serve { 3 } else { 0 };
+        std::char::from_digit(digit, 10).unwrap()
+    }
+
+    pub fn decode(c: char) -> Option<NetInput> {
+        match c.to_digit(10) {
+            Some(d) if d < 6 => Some(NetInput { dir: (d % 3) as int - 1, serve: d >= 3 }),
+            _ => None
+        }
+    }
+}
+
+// moves a paddle as told by the session rather than by the local devices,
+// the session puts the inputs of the tick into Resources::net_inputs
+pub struct NetInputSystem {
+    paddle: uint,
+    side: PaddleSide
+}
+
+impl GlobalSystem for NetInputSystem {
+    fn process(&mut self, entities: &mut [Components], resources: &mut Resources, _: &InputSnapshot) -> () {
+        let input = resources.net_inputs[self.side.index()];
+        if input.serve {
+            resources.serve.request_launch(self.side);
+        }
+        entities[self.paddle].velocity.get_mut_ref().y = input.dir as f64*PADDLE_SPEED;
+    }
+}
+
+// a match driven by NetInputSystems, both sides serve when their input says so
+pub fn add_net_inputs(game: &mut MatchWorld) {
+    for &side in [LEFT, RIGHT].iter() {
+        game.world.resources.serve.human_sides.push(side);
+        let nis = ~NetInputSystem { paddle: game.paddle(side), side: side };
+        game.world.global_systems.push(nis as ~GlobalSystem);
+    }
+}
+
+// everything that decides how a match goes on. Entities are matched up by
+// their place in the world, which is the same for every MatchWorld, and the
+// serve rng is reseeded every tick so it needn't be saved.
+pub struct Snapshot {
+    bodies: ~[(Option<Position>, Option<Velocity>)],
+    serve_state: ServeState,
+    server: PaddleSide,
+    launch_requested: bool,
+    points: [uint, ..2],
+    games: [uint, ..2],
+    sets: [uint, ..2],
+    total_points: [uint, ..2],
+    elapsed: uint,
+    winner: Option<PaddleSide>
+}
+
+fn f64_bits(x: f64) -> u64 {
+    unsafe { std::cast::transmute(x) }
+}
+
+fn f64_from_bits(bits: u64) -> f64 {
+    unsafe { std::cast::transmute(bits) }
+}
+
+impl Snapshot {
+    pub fn capture(game: &MatchWorld) -> Snapshot {
+        Snapshot::of(game.world.entities, &game.world.resources)
+    }
+
+    // for when only the parts of a MatchWorld are at hand, `entities` being
+    // the ones new_match_world made
+    pub fn of(entities: &[Components], resources: &Resources) -> Snapshot {
+        let bodies = entities.iter().map(|e| (e.position, e.velocity)).collect();
+        let (serve, m) = (&resources.serve, &resources.match_state);
+        Snapshot {
+            bodies: bodies,
+            serve_state: serve.state,
+            server: serve.server,
+            launch_requested: serve.launch_requested,
+            points: m.points,
+            games: m.games,
+            sets: m.sets,
+            total_points: m.total_points,
+            elapsed: m.elapsed,
+            winner: m.winner
+        }
+    }
+
+    pub fn restore(&self, game: &mut MatchWorld) {
+        for (e, &(position, velocity)) in game.world.entities.mut_iter().zip(self.bodies.iter()) {
+            if e.position.is_some() && position.is_some() {
+                e.position = position;
+            }
+            if e.velocity.is_some() && velocity.is_some() {
+                e.velocity = velocity;
+            }
+        }
+        let serve = &mut game.world.resources.serve;
+        serve.state = self.serve_state;
+        serve.server = self.server;
+        serve.launch_requested = self.launch_requested;
+        let m = &mut game.world.resources.match_state;
+        m.points = self.points;
+        m.games = self.games;
+        m.sets = self.sets;
+        m.total_points = self.total_points;
+        m.elapsed = self.elapsed;
+        m.winner = self.winner;
+    }
+
+    // the saved values in a fixed order, floats by their bits
+    pub fn values(&self) -> ~[u64] {
+        let mut values = ~[];
+        for &(position, velocity) in self.bodies.iter() {
+            match position {
+                Some(p) => values.push_all([f64_bits(p.x), f64_bits(p.y)]),
+                None => ()
+            }
+            match velocity {
+                Some(v) => values.push_all([f64_bits(v.x), f64_bits(v.y)]),
+                None => ()
+            }
+        }
+        values.push(match self.serve_state {
+            Countdown(ticks) => ticks as u64,
+            WaitingForServe => 1 << 32,
+            InPlay => 2 << 32,
+            OutOfPlay => 3 << 32
+        });
+        values.push(self.server.index() as u64);
+        values.push(if self.launch_requested { 1 } else { 0 });
+        for i in range(0u, 2) {
+            values.push_all([self.points[i] as u64, self.games[i] as u64, self.sets[i] as u64, self.total_points[i] as u64]);
+        }
+        values.push(self.elapsed as u64);
+        values.push(match self.winner {
+            Some(side) => side.index() as u64 + 1,
+            None => 0
+        });
+        values
+    }
+
+    // the snapshot `values` were taken from, for a world set up like the one
+    // this snapshot was captured in. None if they don't fit it.
+    pub fn with_values(&self, values: &[u64]) -> Option<Snapshot> {
+        let body_values = self.bodies.iter().fold(0, |n, &(p, v)| n + if p.is_some() { 2 } else { 0 } + if v.is_some() { 2 } else { 0 });
+        if values.len() != body_values + 13 {
+            return None;
+        }
+        let mut i = 0;
+        let mut bodies = ~[];
+        for &(position, velocity) in self.bodies.iter() {
+            let position = match position {
+                Some(_) => {
+                    i += 2;
+                    Some(Position { x: f64_from_bits(values[i - 2]), y: f64_from_bits(values[i - 1]) })
+                },
+                None => None
+            };
+            let velocity = match velocity {
+                Some(_) => {
+                    i += 2;
+                    Some(Velocity { x: f64_from_bits(values[i - 2]), y: f64_from_bits(values[i - 1]) })
+                },
+                None => None
+            };
+            bodies.push((position, velocity));
+        }
+        let v = values.slice_from(i);
+        let side = |index: u64| if index == 0 { LEFT } else { RIGHT };
+        Some(Snapshot {
+            bodies: bodies,
+            serve_state: match v[0] >> 32 {
+                0 => Countdown(v[0] as uint),
+                1 => WaitingForServe,
+                2 => InPlay,
+                _ => OutOfPlay
+            },
+            server: side(v[1]),
+            launch_requested: v[2] != 0,
+            points: [v[3] as uint, v[7] as uint],
+            games: [v[4] as uint, v[8] as uint],
+            sets: [v[5] as uint, v[9] as uint],
+            total_points: [v[6] as uint, v[10] as uint],
+            elapsed: v[11] as uint,
+            winner: if v[12] == 0 { None } else { Some(side(v[12] - 1)) }
+        })
+    }
+
+    // FNV-1a over the values
+    pub fn checksum(&self) -> u64 {
+        self.values().iter().fold(0xcbf29ce484222325u64, |hash, &v| (hash ^ v)*0x100000001b3)
+    }
+}
+
+// a match world that only shows states from elsewhere, eg. a server
+pub struct MatchView {
+    game: MatchWorld,
+    // `game` as it was set up, for reading values into
+    template: Snapshot
+}
+
+impl MatchView {
+    pub fn new(setup: &MatchSetup) -> MatchView {
+        // the rng is only used by the throwaway tick in `show`
+        let game = new_match_world(setup.rules, setup.serve_rule, ExplicitEuler, XorShiftRng::new());
+        let template = Snapshot::capture(&game);
+        MatchView { game: game, template: template }
+    }
+
+    // puts the state `values` were taken from into the world, false if they
+    // don't fit it
+    pub fn show(&mut self, values: &[u64]) -> bool {
+        match self.template.with_values(values) {
+            Some(snapshot) => {
+                // one tick is run for what the state doesn't hold, such as
+                // the score texts and the serve countdown, and then undone
+                snapshot.restore(&mut self.game);
+                self.game.world.process(&InputSnapshot::none());
+                snapshot.restore(&mut self.game);
+                true
+            },
+            None => false
+        }
+    }
+}
+
+// bad network conditions to try netplay with on one machine, applied to
+// outgoing packets
+#[deriving(Clone)]
+pub struct NetConditions {
+    // one way
+    latency_ms: u64,
+    // fraction of packets dropped
+    loss: f64
+}
+
+impl NetConditions {
+    pub fn new() -> NetConditions {
+        NetConditions { latency_ms: 0, loss: 0.0 }
+    }
+}
+
+// stops once `shutdown` has a message, checked whenever a packet arrives,
+// so that an empty datagram from anyone else can't end it
+fn receive_packets(socket: UdpSocket, packets: Chan<(~[u8], SocketAddr)>, shutdown: Port<()>) {
+    let mut socket = socket;
+    let mut buf = [0u8, ..1500];
+    loop {
+        let received = socket.recvfrom(buf);
+        if shutdown.try_recv().is_some() {
+            break;
+        }
+        match received {
+            Some((0, _)) => (),
+            Some((n, from)) => if !packets.try_send((buf.slice_to(n).to_owned(), from)) { break },
+            None => break
+        }
+    }
+}
+
+// packets are received on the local port in a task of their own, as reading
+// blocks, and sent from a second socket on any free port. Peers always
+// answer to the port the other one listens on.
+pub struct Transport {
+    socket: UdpSocket,
+    local_port: u16,
+    incoming: Port<(~[u8], SocketAddr)>,
+    // tells the receiving task to stop
+    shutdown: Chan<()>,
+    // packets held back by the simulated latency, with the time they're due
+    delayed: ~[(u64, ~[u8], SocketAddr)],
+    conditions: NetConditions,
+    rng: XorShiftRng
+}
+
+impl Transport {
+    pub fn bind(local_port: u16, conditions: NetConditions) -> Option<Transport> {
+        let receive_addr: SocketAddr = from_str(format!("0.0.0.0:{}", local_port)).unwrap();
+        let send_addr: SocketAddr = from_str("0.0.0.0:0").unwrap();
+        // a port that's taken just means there's no transport
+        let bind = |addr: SocketAddr| io_error::cond.trap(|e| {
+            println!("netplay: can't bind {}: {}", addr.to_str(), e.desc);
+        }).inside(|| UdpSocket::bind(addr));
+        match (bind(receive_addr), bind(send_addr)) {
+            (Some(receiver), Some(sender)) => {
+                let (incoming, packets): (Port<(~[u8], SocketAddr)>, Chan<(~[u8], SocketAddr)>) = std::comm::Chan::new();
+                let (stop, shutdown): (Port<()>, Chan<()>) = std::comm::Chan::new();
+                do std::task::spawn {
+                    receive_packets(receiver, packets, stop);
+                }
+                Some(Transport {
+                    socket: sender,
+                    local_port: local_port,
+                    incoming: incoming,
+                    shutdown: shutdown,
+                    delayed: ~[],
+                    conditions: conditions,
+                    rng: XorShiftRng::new()
+                })
+            },
+            (_, _) => None
+        }
+    }
+
+    pub fn send(&mut self, to: SocketAddr, line: &str) {
+        if self.rng.gen::<f64>() < self.conditions.loss {
+            return;
+        }
+        let due = precise_time_ns() + self.conditions.latency_ms*1000000;
+        self.delayed.push((due, line.as_bytes().to_owned(), to));
+        self.flush();
+    }
+
+    // sends the packets whose latency has passed
+    pub fn flush(&mut self) {
+        let now = precise_time_ns();
+        let (due, waiting) = std::util::replace(&mut self.delayed, ~[]).partition(|&(t, _, _)| t <= now);
+        self.delayed = waiting;
+        // a packet that can't be sent is as good as lost
+        io_error::cond.trap(|_| ()).inside(|| {
+            for &(_, ref bytes, to) in due.iter() {
+                self.socket.sendto(bytes.as_slice(), to);
+            }
+        });
+    }
+
+    pub fn receive(&mut self) -> Option<(~str, SocketAddr)> {
+        loop {
+            match self.incoming.try_recv() {
+                Some((bytes, from)) => if std::str::is_utf8(bytes) {
+                    return Some((std::str::from_utf8_owned(bytes), from));
+                },
+                None => return None
+            }
+        }
+    }
+}
+
+impl Drop for Transport {
+    fn drop(&mut self) {
+        // wakes the receiving task up so that it sees the shutdown and lets
+        // go of the port
+        self.shutdown.try_send(());
+        let wake: SocketAddr = from_str(format!("127.0.0.1:{}", self.local_port)).unwrap();
+        io_error::cond.trap(|_| ()).inside(|| self.socket.sendto([], wake));
+    }
+}
+
+// the match the host has chosen
+pub struct MatchSetup {
+    seed: u32,
+    rules: MatchRules,
+    serve_rule: ServeRule
+}
+
+impl MatchSetup {
+    fn to_message(&self) -> ~str {
+        format!("WELCOME {:s}", self.to_words())
+    }
+
+    fn from_message(line: &str) -> Option<MatchSetup> {
+        let words: ~[&str] = line.words().collect();
+        if words.len() > 0 && words[0] == "WELCOME" {
+            MatchSetup::from_words(words.slice_from(1))
+        } else {
+            None
+        }
+    }
+
+    // the setup as seven numbers
+    pub fn to_words(&self) -> ~str {
+        let r = &self.rules;
+        format!("{} {} {} {} {} {} {}", self.seed, r.points_to_win, if r.win_by_two { 1 } else { 0 },
+                r.best_of_games, r.best_of_sets, r.time_limit.unwrap_or(0),
+                match self.serve_rule {
+                    ScorerServes => 0,
+                    ConcederServes => 1
+                })
+    }
+
+    pub fn from_words(words: &[&str]) -> Option<MatchSetup> {
+        if words.len() != 7 {
+            return None;
+        }
+        let numbers: ~[Option<uint>] = words.iter().map(|w| from_str(*w)).collect();
+        if numbers.iter().any(|n| n.is_none()) {
+            return None;
+        }
+        let n: ~[uint] = numbers.iter().map(|n| n.unwrap()).collect();
+        Some(MatchSetup {
+            seed: n[0] as u32,
+            rules: MatchRules {
+                points_to_win: n[1],
+                win_by_two: n[2] != 0,
+                best_of_games: n[3],
+                best_of_sets: n[4],
+                time_limit: if n[5] == 0 { None } else { Some(n[5]) }
+            },
+            serve_rule: if n[6] == 0 { ScorerServes } else { ConcederServes }
+        })
+    }
+}
+
+#[deriving(Clone)]
+pub enum NetRole {
+    // waits for a peer and plays the left paddle
+    Host,
+    // the host's address, plays the right paddle
+    Join(SocketAddr)
+}
+
+#[deriving(Clone)]
+pub struct NetplayConfig {
+    role: NetRole,
+    local_port: u16,
+    conditions: NetConditions,
+    // ticks between an input and the tick it's used for
+    input_delay: uint
+}
+
+// what the lobby hands over to the match
+pub struct Connection {
+    transport: Transport,
+    peer: SocketAddr,
+    setup: MatchSetup,
+    local_side: PaddleSide,
+    // answer to a repeated HELLO, for a host whose WELCOME got lost
+    welcome: Option<~str>
+}
+
+// the handshake before a match: the joining peer says HELLO until the host
+// answers with the match to play
+pub struct Lobby {
+    config: NetplayConfig,
+    transport: Option<Transport>,
+    // only used by the host
+    setup: MatchSetup,
+    ticks: uint
+}
+
+impl Lobby {
+    pub fn new(config: NetplayConfig, setup: MatchSetup) -> Lobby {
+        let transport = Transport::bind(config.local_port, config.conditions.clone());
+        if transport.is_none() {
+            println!("netplay: can't listen on port {}", config.local_port);
+        }
+        Lobby { config: config, transport: transport, setup: setup, ticks: 0 }
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.transport.is_some()
+    }
+
+    // the connection once a peer has been found
+    pub fn poll(&mut self) -> Option<Connection> {
+        let found = match self.transport {
+            Some(ref mut transport) => {
+                if self.ticks % HELLO_INTERVAL == 0 {
+                    match self.config.role {
+                        Join(host) => transport.send(host, format!("HELLO {}", self.config.local_port)),
+                        Host => ()
+                    }
+                }
+                self.ticks += 1;
+                let mut found = None;
+                while found.is_none() {
+                    let (line, from) = match transport.receive() {
+                        Some(packet) => packet,
+                        None => break
+                    };
+                    found = match self.config.role {
+                        Host => match parse_hello(line) {
+                            Some(port) => {
+                                let peer = SocketAddr { ip: from.ip, port: port };
+                                let welcome = self.setup.to_message();
+                                transport.send(peer, welcome);
+                                Some((peer, self.setup, LEFT, Some(welcome)))
+                            },
+                            None => None
+                        },
+                        Join(host) => MatchSetup::from_message(line).map(|setup| (host, setup, RIGHT, None))
+                    };
+                }
+                transport.flush();
+                found
+            },
+            None => None
+        };
+        match found {
+            Some((peer, setup, side, welcome)) => {
+                println!("netplay: connected to {}", peer.to_str());
+                Some(Connection {
+                    transport: self.transport.take_unwrap(),
+                    peer: peer,
+                    setup: setup,
+                    local_side: side,
+                    welcome: welcome
+                })
+            },
+            None => None
+        }
+    }
+}
+
+fn parse_hello(line: &str) -> Option<u16> {
+    let words: ~[&str] = line.words().collect();
+    if words.len() == 2 && words[0] == "HELLO" {
+        from_str(words[1])
+    } else {
+        None
+    }
+}
+
+// runs a match against a peer, see the top of the file
+pub struct RollbackSession {
+    game: MatchWorld,
+    seed: u32,
+    local_side: PaddleSide,
+    transport: Transport,
+    peer: SocketAddr,
+    welcome: Option<~str>,
+    // the next tick to run
+    tick: uint,
+    // by tick, starting with idle ones for the input delay
+    local_inputs: ~[NetInput],
+    // by tick, as far as they have arrived
+    remote_inputs: ~[Option<NetInput>],
+    // remote inputs have arrived for every tick before this
+    remote_known: uint,
+    // the remote input each tick was run with, known or guessed
+    used_remote: ~[NetInput],
+    // the peer has every local input before this tick
+    acked: uint,
+    // the state before each of the last ticks, at tick % SNAPSHOTS
+    snapshots: ~[Option<(uint, Snapshot)>],
+    // the earliest tick that was run with a wrong guess
+    rollback_to: Option<uint>,
+    next_check: uint,
+    // (tick, checksum) of the state before the tick, for the last few checks
+    checksums: ~[(uint, u64)],
+    remote_checksums: ~[(uint, u64)],
+    desynced: bool,
+    last_heard: u64,
+    // how often a guess was wrong, for the status line
+    rollbacks: uint
+}
+
+impl RollbackSession {
+    pub fn new(connection: Connection, input_delay: uint) -> RollbackSession {
+        let Connection { transport: transport, peer: peer, setup: setup, local_side: local_side, welcome: welcome } = connection;
+        // both peers have to run the same physics, so it isn't a setting
+        let mut game = new_match_world(setup.rules, setup.serve_rule, ExplicitEuler, seeded_rng(setup.seed));
+        add_net_inputs(&mut game);
+        RollbackSession {
+            game: game,
+            seed: setup.seed,
+            local_side: local_side,
+            transport: transport,
+            peer: peer,
+            welcome: welcome,
+            tick: 0,
+            local_inputs: std::vec::from_elem(input_delay, NetInput::idle()),
+            remote_inputs: ~[],
+            remote_known: 0,
+            used_remote: ~[],
+            acked: 0,
+            snapshots: std::vec::from_fn(SNAPSHOTS, |_| None),
+            rollback_to: None,
+            next_check: CHECK_INTERVAL,
+            checksums: ~[],
+            remote_checksums: ~[],
+            desynced: false,
+            last_heard: precise_time_ns(),
+            rollbacks: 0
+        }
+    }
+
+    // called once a frame with the local player's input
+    pub fn update(&mut self, local: NetInput) {
+        self.receive();
+        self.roll_back();
+        // the match can't run further ahead than can be rolled back
+        if !self.is_waiting() {
+            self.local_inputs.push(local);
+            self.run_tick();
+        }
+        self.check();
+        self.send_inputs();
+        self.transport.flush();
+    }
+
+    // the remote inputs are too far behind to go on guessing
+    pub fn is_waiting(&self) -> bool {
+        self.tick >= self.remote_known + MAX_ROLLBACK
+    }
+
+    // the winner once both peers agree that the match is over
+    pub fn winner(&self) -> Option<PaddleSide> {
+        if self.remote_known >= self.tick {
+            self.game.world.resources.match_state.winner
+        } else {
+            None
+        }
+    }
+
+    pub fn is_desynced(&self) -> bool {
+        self.desynced
+    }
+
+    pub fn peer_lost(&self) -> bool {
+        precise_time_ns() - self.last_heard > PEER_TIMEOUT_NS
+    }
+
+    fn remote_input(&self, tick: uint) -> NetInput {
+        if tick < self.remote_known {
+            self.remote_inputs[tick].unwrap()
+        } else if self.remote_known > 0 {
+            self.remote_inputs[self.remote_known - 1].unwrap()
+        } else {
+            NetInput::idle()
+        }
+    }
+
+    fn run_tick(&mut self) {
+        let t = self.tick;
+        self.snapshots[t % SNAPSHOTS] = Some((t, Snapshot::capture(&self.game)));
+        let remote = self.remote_input(t);
+        {
+            let resources = &mut self.game.world.resources;
+            resources.net_inputs[self.local_side.index()] = self.local_inputs[t];
+            resources.net_inputs[self.local_side.opponent().index()] = remote;
+            resources.rng = seeded_rng(self.seed + t as u32);
+        }
+        self.game.world.process(&InputSnapshot::none());
+        if t < self.used_remote.len() {
+            self.used_remote[t] = remote;
+        } else {
+            self.used_remote.push(remote);
+        }
+        self.tick += 1;
+    }
+
+    fn roll_back(&mut self) {
+        let from = match self.rollback_to.take() {
+            Some(from) => from,
+            None => return
+        };
+        match self.snapshots[from % SNAPSHOTS] {
+            Some((t, ref snapshot)) if t == from => snapshot.restore(&mut self.game),
+            _ => fail!("netplay: no state saved for tick {}", from)
+        }
+        let end = self.tick;
+        self.tick = from;
+        while self.tick < end {
+            self.run_tick();
+        }
+        self.rollbacks += 1;
+    }
+
+    fn receive(&mut self) {
+        loop {
+            let (line, from) = match self.transport.receive() {
+                Some(packet) => packet,
+                None => break
+            };
+            if from.ip != self.peer.ip {
+                continue;
+            }
+            self.last_heard = precise_time_ns();
+            let words: ~[&str] = line.words().collect();
+            if words.len() == 2 && words[0] == "HELLO" {
+                let welcome = self.welcome.clone();
+                match welcome {
+                    Some(w) => self.transport.send(self.peer, w),
+                    None => ()
+                }
+            } else if words.len() == 6 && words[0] == "INPUT" {
+                match (from_str::<uint>(words[1]), from_str::<uint>(words[3]),
+                       from_str::<uint>(words[4]), from_str::<u64>(words[5])) {
+                    (Some(first), Some(ack), Some(check_tick), Some(checksum)) => {
+                        self.inputs_arrived(first, words[2]);
+                        self.acked = self.acked.max(&ack);
+                        self.checksum_arrived(check_tick, checksum);
+                    },
+                    (_, _, _, _) => ()
+                }
+            }
+        }
+    }
+
+    fn inputs_arrived(&mut self, first: uint, codes: &str) {
+        for (i, c) in codes.chars().enumerate() {
+            let t = first + i;
+            // the peer can't be further ahead than this, anything else is garbage
+            if t >= self.tick + MAX_ROLLBACK + MAX_PACKET_INPUTS {
+                break;
+            }
+            let input = match NetInput::decode(c) {
+                Some(input) => input,
+                None => break
+            };
+            while self.remote_inputs.len() <= t {
+                self.remote_inputs.push(None);
+            }
+            if self.remote_inputs[t].is_some() {
+                continue;
+            }
+            self.remote_inputs[t] = Some(input);
+            if t < self.tick && self.used_remote[t] != input {
+                self.rollback_to = Some(match self.rollback_to {
+                    Some(from) => from.min(&t),
+                    None => t
+                });
+            }
+        }
+        while self.remote_known < self.remote_inputs.len() && self.remote_inputs[self.remote_known].is_some() {
+            self.remote_known += 1;
+        }
+    }
+
+    fn checksum_arrived(&mut self, tick: uint, checksum: u64) {
+        // tick 0 is never checked, it stands for no checksum yet
+        if tick == 0 || self.remote_checksums.iter().any(|&(t, _)| t == tick) {
+            return;
+        }
+        self.remote_checksums.push((tick, checksum));
+        if self.remote_checksums.len() > CHECKSUMS {
+            self.remote_checksums.shift();
+        }
+    }
+
+    // checksums the states that only depend on known inputs and compares
+    // them with the peer's
+    fn check(&mut self) {
+        while self.next_check <= self.remote_known && self.next_check < self.tick {
+            let c = self.next_check;
+            match self.snapshots[c % SNAPSHOTS] {
+                Some((t, ref snapshot)) if t == c => {
+                    self.checksums.push((c, snapshot.checksum()));
+                    if self.checksums.len() > CHECKSUMS {
+                        self.checksums.shift();
+                    }
+                },
+                _ => ()
+            }
+            self.next_check += CHECK_INTERVAL;
+        }
+        if self.desynced {
+            return;
+        }
+        for &(tick, remote) in self.remote_checksums.iter() {
+            match self.checksums.iter().find(|& &(t, _)| t == tick) {
+                Some(&(_, local)) if local != remote => {
+                    println!("netplay: desync at tick {}, checksum {:x} here and {:x} on the peer", tick, local, remote);
+                    self.desynced = true;
+                    return;
+                },
+                _ => ()
+            }
+        }
+    }
+
+    fn send_inputs(&mut self) {
+        let end = self.local_inputs.len();
+        let first = self.acked.min(&end);
+        let last = end.min(&(first + MAX_PACKET_INPUTS));
+        let codes: ~str = if first == last {
+            ~"-"
+        } else {
+            self.local_inputs.slice(first, last).iter().map(|i| i.encode()).collect()
+        };
+        let (check_tick, checksum) = if self.checksums.is_empty() {
+            (0, 0)
+        } else {
+            self.checksums[self.checksums.len() - 1]
+        };
+        let packet = format!("INPUT {} {} {} {} {}", first, codes, self.remote_known, check_tick, checksum);
+        self.transport.send(self.peer, packet);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use game::{new_match_world,seeded_rng};
+    use input::InputSnapshot;
+    use netplay::*;
+    use resources::*;
+
+    #[test]
+    fn snapshot_values_round_trip() {
+        let template = Snapshot::capture(&new_match_world(MatchRules::new(), ScorerServes, ExplicitEuler, seeded_rng(1)));
+        let mut game = new_match_world(MatchRules::new(), ScorerServes, ExplicitEuler, seeded_rng(1));
+        // past the serve countdown so that the ball is moving
+        for _ in range(0, 240) {
+            game.world.process(&InputSnapshot::none());
+        }
+        let snapshot = Snapshot::capture(&game);
+        let values = snapshot.values();
+        let restored = template.with_values(values).unwrap();
+        assert_eq!(restored.values(), values);
+        assert_eq!(restored.checksum(), snapshot.checksum());
+        assert!(template.checksum() != snapshot.checksum());
+    }
+
+    #[test]
+    fn values_that_dont_fit_are_refused() {
+        let template = Snapshot::capture(&new_match_world(MatchRules::new(), ScorerServes, ExplicitEuler, seeded_rng(1)));
+        let values = template.values();
+        assert!(template.with_values(values.slice_to(values.len() - 1)).is_none());
+        let mut longer = values.clone();
+        longer.push(0);
+        assert!(template.with_values(longer).is_none());
+    }
+}
@@ -1,6 +1,9 @@
 #[feature(globs)];
 #[feature(macro_rules)];
-#[feature(managed_boxes)]; //TODO do without managed boxes
+// only the windowed ui shares state through managed boxes: the StateStack's
+// states, the RenderSystem with its shaders and debug layer, Settings and the
+// console commands. Worlds and everything in the library are owned.
+#[feature(managed_boxes)];
 
 extern mod glfw;
 extern mod gl;
@@ -8,15 +11,35 @@ extern mod native;
 
 use main::main;
 
+mod ai;
+mod broadcast;
+mod batch;
+mod bindings;
 mod callbacks;
+mod client;
+mod collision;
 mod components;
+mod console;
 mod entities;
+mod env;
+mod extbot;
+mod gamepad;
+mod game;
 mod globalsystems;
 mod graphics;
+mod input;
 mod main;
+mod menu;
+mod netplay;
+mod resources;
+mod server;
+mod settings;
+mod states;
 mod systems;
+mod tournament;
 mod world;
 
+#[cfg(not(test))]
 #[start]
 fn start(argc: int, argv: **u8) -> int {
     native::start(argc, argv, main)
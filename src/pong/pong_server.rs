@@ -0,0 +1,31 @@
+// the dedicated match server, see server.rs. It never opens a window and
+// doesn't link glfw or gl.
+#[feature(globs)];
+#[feature(macro_rules)];
+
+extern mod native;
+
+mod ai;
+mod broadcast;
+mod collision;
+mod components;
+mod entities;
+mod extbot;
+mod game;
+mod globalsystems;
+mod input;
+mod netplay;
+mod resources;
+mod server;
+mod systems;
+mod world;
+
+#[start]
+fn start(argc: int, argv: **u8) -> int {
+    native::start(argc, argv, main)
+}
+
+fn main() {
+    let args = std::os::args();
+    server::run(args.slice_from(1));
+}
@@ -0,0 +1,446 @@
+// RESOURCE DEFINITIONS
+// world-wide state and configuration, shared by the systems that need it
+extern mod std;
+use std::rand::{Rng,XorShiftRng};
+use ai::{BotDifficulty,Normal};
+use collision::CollisionEvents;
+use entities::{PaddleSide,LEFT,RIGHT};
+use netplay::NetInput;
+
+#[deriving(Eq, Clone)]
+pub enum Integrator {
+    // position from the old velocity, then velocity
+    ExplicitEuler,
+    // velocity first, then position from the new velocity
+    SemiImplicitEuler,
+    Verlet
+}
+
+pub static INTEGRATORS: [Integrator, ..3] = [ExplicitEuler, SemiImplicitEuler, Verlet];
+
+impl Integrator {
+    pub fn label(&self) -> &'static str {
+        match *self {
+            ExplicitEuler => "EXPLICIT EULER",
+            SemiImplicitEuler => "SEMI-IMPLICIT EULER",
+            Verlet => "VERLET"
+        }
+    }
+}
+
+// physics settings that apply to the whole world, kept fixed during a run
+// so that results are reproducible
+pub struct WorldPhysics {
+    integrator: Integrator,
+    // in arena units per tick per tick
+    gravity: (f64, f64)
+}
+
+impl WorldPhysics {
+    pub fn new() -> WorldPhysics {
+        WorldPhysics {
+            integrator: ExplicitEuler,
+            gravity: (0.0, 0.0)
+        }
+    }
+}
+
+pub enum BounceModel {
+    // the original behaviour: horizontal speed is kept and vertical speed
+    // grows with sinh of the hit offset
+    ClassicBounce,
+    // the hit offset picks the return angle, up to max_bounce_angle at the
+    // paddle's ends, and the ball keeps its speed
+    OffsetAngleBounce
+}
+
+pub struct BallPhysics {
+    // all speeds are in arena units per tick
+    serve_speed: f64,
+    // added to the ball's speed on every paddle hit
+    speed_increase: f64,
+    max_speed: f64,
+    bounce_model: BounceModel,
+    // radians from horizontal
+    max_bounce_angle: f64,
+    // fraction of the paddle's vertical velocity passed on to the ball,
+    // 0.0 disables spin
+    spin: f64,
+    // serves leave at a random angle within +-serve_angle radians
+    serve_angle: f64
+}
+
+impl BallPhysics {
+    pub fn new() -> BallPhysics {
+        BallPhysics {
+            serve_speed: 1.0/60.0,
+            speed_increase: 0.05/60.0,
+            max_speed: 3.0/60.0,
+            bounce_model: OffsetAngleBounce,
+            max_bounce_angle: std::f64::consts::PI/3.0,
+            spin: 0.0,
+            serve_angle: std::f64::consts::PI/6.0
+        }
+    }
+
+    // `normal_x` is the direction the ball leaves the paddle in and `offset` where the
+    // ball hit the paddle, from -1.0 at the bottom end to 1.0 at the top end
+    pub fn paddle_bounce(&self, velocity: (f64, f64), normal_x: f64, offset: f64, paddle_velocity: f64) -> (f64, f64) {
+        let (vx, vy) = velocity;
+        match self.bounce_model {
+            ClassicBounce => {
+                let hx = normal_x*(std::num::abs(vx) + self.speed_increase).min(&self.max_speed);
+                (hx, 0.5*hx*std::num::sinh(3.14*offset) + self.spin*paddle_velocity)
+            },
+            OffsetAngleBounce => {
+                let speed = ((vx*vx + vy*vy).sqrt() + self.speed_increase).min(&self.max_speed);
+                let angle = offset.max(&-1.0).min(&1.0)*self.max_bounce_angle;
+                let hx = normal_x*speed*angle.cos();
+                let hy = speed*angle.sin() + self.spin*paddle_velocity;
+                // spin changes the angle but not the speed
+                let scale = speed/(hx*hx + hy*hy).sqrt();
+                (hx*scale, hy*scale)
+            }
+        }
+    }
+
+    // `direction` is 1.0 to serve right and -1.0 to serve left
+    pub fn serve_velocity<R: Rng>(&self, direction: f64, rng: &mut R) -> (f64, f64) {
+        let angle = if self.serve_angle > 0.0 {
+            rng.gen_range(-self.serve_angle, self.serve_angle)
+        } else {
+            0.0
+        };
+        (direction*self.serve_speed*angle.cos(), self.serve_speed*angle.sin())
+    }
+}
+
+#[deriving(Eq)]
+pub enum ServeState {
+    // counting down before the serve, in ticks left
+    Countdown(uint),
+    // ball is held by a human server until they launch it
+    WaitingForServe,
+    InPlay,
+    // no serve is coming, eg. once the match is over
+    OutOfPlay
+}
+
+pub enum ServeRule {
+    ScorerServes,
+    ConcederServes
+}
+
+// the serve state machine, driven by ServeSystem
+pub struct ServeControl {
+    state: ServeState,
+    server: PaddleSide,
+    rule: ServeRule,
+    countdown_ticks: uint,
+    // servers on these sides launch the ball themselves after the countdown
+    human_sides: ~[PaddleSide],
+    launch_requested: bool
+}
+
+impl ServeControl {
+    pub fn new(server: PaddleSide, rule: ServeRule) -> ServeControl {
+        ServeControl {
+            state: Countdown(180),
+            server: server,
+            rule: rule,
+            countdown_ticks: 180,
+            human_sides: ~[],
+            launch_requested: false
+        }
+    }
+
+    pub fn is_in_play(&self) -> bool {
+        self.state == InPlay
+    }
+
+    pub fn server_is_human(&self) -> bool {
+        self.human_sides.contains(&self.server)
+    }
+
+    // called when `scorer` has scored a point
+    pub fn point_scored(&mut self, scorer: PaddleSide) {
+        self.server = match (self.rule, scorer) {
+            (ScorerServes, side) => side,
+            (ConcederServes, LEFT) => RIGHT,
+            (ConcederServes, RIGHT) => LEFT
+        };
+        self.state = Countdown(self.countdown_ticks);
+        self.launch_requested = false;
+    }
+
+    // only has an effect when `side` is serving
+    pub fn request_launch(&mut self, side: PaddleSide) {
+        if side == self.server && self.state != InPlay && self.state != OutOfPlay {
+            self.launch_requested = true;
+        }
+    }
+}
+
+pub struct MatchRules {
+    // points needed to win a game
+    points_to_win: uint,
+    // a game needs to be won by two clear points, ie. deuce
+    win_by_two: bool,
+    // a set is best of this many games
+    best_of_games: uint,
+    // the match is best of this many sets
+    best_of_sets: uint,
+    // in ticks, when time runs out whoever leads wins, or the next point
+    // decides if it's a tie
+    time_limit: Option<uint>
+}
+
+impl MatchRules {
+    pub fn new() -> MatchRules {
+        MatchRules {
+            points_to_win: 11,
+            win_by_two: true,
+            best_of_games: 3,
+            best_of_sets: 1,
+            time_limit: None
+        }
+    }
+}
+
+// progress of the current match, all per-side arrays are indexed with PaddleSide::index
+pub struct MatchState {
+    rules: MatchRules,
+    points: [uint, ..2],
+    games: [uint, ..2],
+    sets: [uint, ..2],
+    // every point won during the match, unlike `points` these are never reset
+    total_points: [uint, ..2],
+    // in ticks
+    elapsed: uint,
+    winner: Option<PaddleSide>
+}
+
+impl MatchState {
+    pub fn new(rules: MatchRules) -> MatchState {
+        MatchState {
+            rules: rules,
+            points: [0, 0],
+            games: [0, 0],
+            sets: [0, 0],
+            total_points: [0, 0],
+            elapsed: 0,
+            winner: None
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    pub fn is_deuce(&self) -> bool {
+        self.rules.win_by_two
+            && self.points[0] == self.points[1]
+            && self.points[0] + 1 >= self.rules.points_to_win
+    }
+
+    pub fn time_is_up(&self) -> bool {
+        match self.rules.time_limit {
+            Some(limit) => self.elapsed >= limit,
+            None => false
+        }
+    }
+
+    pub fn point_scored(&mut self, side: PaddleSide) {
+        if self.is_over() {
+            return
+        }
+        let (i, o) = (side.index(), side.opponent().index());
+        self.points[i] += 1;
+        self.total_points[i] += 1;
+        let lead_ok = !self.rules.win_by_two || self.points[i] >= self.points[o] + 2;
+        if self.points[i] >= self.rules.points_to_win && lead_ok {
+            self.points = [0, 0];
+            self.games[i] += 1;
+            if self.games[i] > self.rules.best_of_games/2 {
+                self.games = [0, 0];
+                self.sets[i] += 1;
+                if self.sets[i] > self.rules.best_of_sets/2 {
+                    self.finish(side);
+                    return
+                }
+            }
+        }
+        if self.time_is_up() {
+            self.check_time();
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if self.is_over() {
+            return
+        }
+        self.elapsed += 1;
+        if self.time_is_up() {
+            self.check_time();
+        }
+    }
+
+    // the side that is ahead on sets, then games, then points
+    pub fn leader(&self) -> Option<PaddleSide> {
+        let left = (self.sets[0], self.games[0], self.points[0]);
+        let right = (self.sets[1], self.games[1], self.points[1]);
+        if left > right {
+            Some(LEFT)
+        } else if right > left {
+            Some(RIGHT)
+        } else {
+            None
+        }
+    }
+
+    fn check_time(&mut self) {
+        match self.leader() {
+            Some(side) => self.finish(side),
+            None => ()
+        }
+    }
+
+    fn finish(&mut self, winner: PaddleSide) {
+        self.winner = Some(winner);
+    }
+}
+
+// everything in a world that isn't an entity, handed to the systems along
+// with the entities. Per-side arrays are indexed with PaddleSide::index.
+pub struct Resources {
+    physics: WorldPhysics,
+    ball_physics: BallPhysics,
+    serve: ServeControl,
+    match_state: MatchState,
+    // found by CollisionSystem during the current tick
+    collisions: CollisionEvents,
+    // for serves and bots, netplay reseeds it every tick
+    rng: XorShiftRng,
+    bot_difficulty: [BotDifficulty, ..2],
+    // where each bot is sending its paddle, for the debug layer
+    bot_targets: [Option<(f64, f64)>, ..2],
+    // the inputs of the tick being run for paddles driven over the network,
+    // see netplay::NetInputSystem
+    net_inputs: [NetInput, ..2]
+}
+
+impl Resources {
+    // for a world without a match in it, new_match_world sets the match up
+    pub fn new() -> Resources {
+        Resources {
+            physics: WorldPhysics::new(),
+            ball_physics: BallPhysics::new(),
+            serve: ServeControl::new(LEFT, ScorerServes),
+            match_state: MatchState::new(MatchRules::new()),
+            collisions: CollisionEvents::new(),
+            rng: XorShiftRng::new(),
+            bot_difficulty: [Normal.difficulty(), Normal.difficulty()],
+            bot_targets: [None, None],
+            net_inputs: [NetInput::idle(), NetInput::idle()]
+        }
+    }
+}
+
+#[deriving(Eq)]
+pub enum GameMode {
+    // human on the left paddle
+    HumanVsBot,
+    HumanVsHuman,
+    BotVsBot
+}
+
+pub static GAME_MODES: [GameMode, ..3] = [HumanVsBot, HumanVsHuman, BotVsBot];
+
+impl GameMode {
+    pub fn is_human(&self, side: PaddleSide) -> bool {
+        match (*self, side) {
+            (HumanVsHuman, _) | (HumanVsBot, LEFT) => true,
+            (_, _) => false
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match *self {
+            HumanVsBot => "HUMAN VS BOT",
+            HumanVsHuman => "HUMAN VS HUMAN",
+            BotVsBot => "BOT VS BOT"
+        }
+    }
+
+    // as given on the command line
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        match name {
+            "hvb" => Some(HumanVsBot),
+            "hvh" => Some(HumanVsHuman),
+            "bvb" => Some(BotVsBot),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use entities::{PaddleSide,LEFT,RIGHT};
+    use resources::*;
+
+    fn score(m: &mut MatchState, side: PaddleSide, points: uint) {
+        for _ in range(0, points) {
+            m.point_scored(side);
+        }
+    }
+
+    #[test]
+    fn deuce_needs_two_clear_points() {
+        let mut m = MatchState::new(MatchRules::new());
+        score(&mut m, LEFT, 10);
+        score(&mut m, RIGHT, 10);
+        assert!(m.is_deuce());
+        score(&mut m, LEFT, 1);
+        assert_eq!(m.games.as_slice(), &[0, 0]);
+        score(&mut m, RIGHT, 1);
+        assert!(m.is_deuce());
+        score(&mut m, RIGHT, 2);
+        assert_eq!(m.games.as_slice(), &[0, 1]);
+        assert_eq!(m.points.as_slice(), &[0, 0]);
+    }
+
+    #[test]
+    fn no_deuce_without_win_by_two() {
+        let mut rules = MatchRules::new();
+        rules.win_by_two = false;
+        let mut m = MatchState::new(rules);
+        score(&mut m, LEFT, 10);
+        score(&mut m, RIGHT, 10);
+        assert!(!m.is_deuce());
+        score(&mut m, RIGHT, 1);
+        assert_eq!(m.games.as_slice(), &[0, 1]);
+    }
+
+    #[test]
+    fn best_of_games_and_sets() {
+        let mut rules = MatchRules::new();
+        rules.points_to_win = 1;
+        rules.win_by_two = false;
+        rules.best_of_games = 3;
+        rules.best_of_sets = 3;
+        let mut m = MatchState::new(rules);
+        score(&mut m, LEFT, 2);
+        assert_eq!(m.sets.as_slice(), &[1, 0]);
+        assert_eq!(m.games.as_slice(), &[0, 0]);
+        score(&mut m, RIGHT, 1);
+        score(&mut m, LEFT, 1);
+        score(&mut m, RIGHT, 1);
+        assert_eq!(m.sets.as_slice(), &[1, 1]);
+        assert!(!m.is_over());
+        score(&mut m, LEFT, 2);
+        assert_eq!(m.winner, Some(LEFT));
+        // points after the match is over don't count
+        score(&mut m, RIGHT, 1);
+        assert_eq!(m.total_points.as_slice(), &[5, 2]);
+    }
+}
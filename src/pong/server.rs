@@ -0,0 +1,499 @@
+// DEDICATED SERVER
+// a headless server that owns the match. Clients only send their inputs and
+// the server sends every client the state of each tick, as the values that
+// changed since the newest state the client has acknowledged. Players see
+// their own paddle ahead of the server, see client.rs. Anyone past the two
+// players watches as a spectator.
+//
+// Packets are single lines of text, from a client:
+//   JOIN <port> <play|watch>
+//   INPUT <port> <first seq> <inputs> <match> <tick>
+//   ACK <port> <match> <tick>
+// where port is the one the client listens on, which tells clients on the
+// same machine apart, inputs are as in netplay.rs and resent until applied,
+// and match and tick are of the newest state the client has received. From
+// the server:
+//   ACCEPT <left|right|watch> <setup>  the setup as in MatchSetup::to_words
+//   FULL
+//   STATE <match> <tick> <base> <seq> <values>
+// where values are `index:value` pairs in hex of the Snapshot values that
+// differ from the state of tick `base`, or all of them when base is `-`, and
+// seq is the player's last input applied to the match.
+extern mod extra;
+extern mod std;
+use extra::time::precise_time_ns;
+use std::io::net::ip::SocketAddr;
+use std::rand::{Rng,XorShiftRng};
+use broadcast::{BroadcastConfig,Broadcaster,BroadcastSystem};
+use entities::{PaddleSide,LEFT,RIGHT};
+use game::{MatchWorld,new_match_world};
+use globalsystems::GlobalSystem;
+use input::InputSnapshot;
+use netplay::*;
+use resources::*;
+
+// server ticks per second
+static TICK_RATE: u64 = 60;
+// states kept as bases for deltas
+pub static STATE_HISTORY: uint = 64;
+// a client that hasn't been heard from for this long is gone
+static CLIENT_TIMEOUT_NS: u64 = 5000000000;
+// how long the result is shown before the next match, in ticks
+static RESULT_TICKS: uint = 300;
+// inputs a player may get ahead of the server before older ones are skipped
+static INPUT_BUFFER: uint = 8;
+// inputs waiting to be applied at most, anything further ahead is garbage
+static MAX_PENDING_INPUTS: uint = 256;
+
+#[deriving(Eq, Clone)]
+pub enum ClientRole {
+    Player(PaddleSide),
+    Spectator
+}
+
+impl ClientRole {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Player(LEFT) => "left",
+            Player(RIGHT) => "right",
+            Spectator => "watch"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ClientRole> {
+        match name {
+            "left" => Some(Player(LEFT)),
+            "right" => Some(Player(RIGHT)),
+            "watch" => Some(Spectator),
+            _ => None
+        }
+    }
+}
+
+// `index:value` pairs of the values that differ from `base`, or all of them
+pub fn encode_values(values: &[u64], base: Option<&[u64]>) -> ~str {
+    let mut pairs = ~[];
+    for (i, &v) in values.iter().enumerate() {
+        match base {
+            Some(b) if i < b.len() && b[i] == v => (),
+            _ => pairs.push(format!("{:x}:{:x}", i, v))
+        }
+    }
+    if pairs.is_empty() {
+        ~"-"
+    } else {
+        pairs.connect(",")
+    }
+}
+
+// the values `encoded` stands for on top of `base`, which has to be the full
+// list when the encoding is a delta
+pub fn decode_values(encoded: &str, base: ~[u64]) -> Option<~[u64]> {
+    let mut values = base;
+    if encoded == "-" {
+        return Some(values);
+    }
+    for pair in encoded.split(',') {
+        let parts: ~[&str] = pair.split(':').collect();
+        if parts.len() != 2 {
+            return None;
+        }
+        match (std::num::from_str_radix::<uint>(parts[0], 16), std::num::from_str_radix::<u64>(parts[1], 16)) {
+            (Some(i), Some(v)) if i < values.len() => values[i] = v,
+            (Some(i), Some(v)) if i == values.len() => values.push(v),
+            (_, _) => return None
+        }
+    }
+    Some(values)
+}
+
+pub struct ServerConfig {
+    port: u16,
+    max_spectators: uint,
+    rules: MatchRules,
+    serve_rule: ServeRule,
+    conditions: NetConditions,
+    broadcast: BroadcastConfig
+}
+
+impl ServerConfig {
+    pub fn new() -> ServerConfig {
+        ServerConfig {
+            port: 7000,
+            max_spectators: 8,
+            rules: MatchRules::new(),
+            serve_rule: ScorerServes,
+            conditions: NetConditions::new(),
+            broadcast: BroadcastConfig::new()
+        }
+    }
+}
+
+struct ServerClient {
+    // where the client listens
+    addr: SocketAddr,
+    role: ClientRole,
+    // inputs from `next_seq` on, as far as they have arrived
+    pending: ~[Option<NetInput>],
+    next_seq: uint,
+    // used again while the next input is missing
+    last_input: NetInput,
+    // the newest state the client has, as (match, tick)
+    acked: Option<(uint, uint)>,
+    last_heard: u64
+}
+
+impl ServerClient {
+    fn inputs_arrived(&mut self, first: uint, codes: &str) {
+        // the client has given up on resending the inputs before `first`
+        if first > self.next_seq {
+            let skipped = (first - self.next_seq).min(&self.pending.len());
+            self.pending = self.pending.slice_from(skipped).to_owned();
+            self.next_seq = first;
+        }
+        for (i, c) in codes.chars().enumerate() {
+            let seq = first + i;
+            if seq < self.next_seq {
+                continue;
+            }
+            let index = seq - self.next_seq;
+            if index >= MAX_PENDING_INPUTS {
+                break;
+            }
+            let input = match NetInput::decode(c) {
+                Some(input) => input,
+                None => break
+            };
+            while self.pending.len() <= index {
+                self.pending.push(None);
+            }
+            self.pending[index] = Some(input);
+        }
+    }
+
+    // the input for the next tick. A player whose clock runs ahead of the
+    // server skips inputs rather than falling further and further behind.
+    fn next_input(&mut self) -> NetInput {
+        while self.pending.len() > INPUT_BUFFER {
+            match self.pending.shift() {
+                Some(input) => self.last_input = input,
+                None => ()
+            }
+            self.next_seq += 1;
+        }
+        if !self.pending.is_empty() && self.pending[0].is_some() {
+            self.last_input = self.pending.shift().unwrap();
+            self.next_seq += 1;
+        }
+        self.last_input
+    }
+
+    // takes the inputs that arrived while the match wasn't running as
+    // applied, so that the player's paddle isn't predicted to move
+    fn discard_inputs(&mut self) {
+        self.next_seq += self.pending.len();
+        self.pending.clear();
+        self.last_input = NetInput::idle();
+    }
+}
+
+pub struct Server {
+    config: ServerConfig,
+    transport: Transport,
+    clients: ~[ServerClient],
+    game: MatchWorld,
+    broadcaster: Option<Broadcaster>,
+    setup: MatchSetup,
+    // the values of the last few states at tick % STATE_HISTORY
+    history: ~[Option<(uint, ~[u64])>],
+    match_id: uint,
+    tick: uint,
+    // ticks since the match ended
+    result_ticks: uint
+}
+
+impl Server {
+    pub fn new(config: ServerConfig, transport: Transport) -> Server {
+        let setup = MatchSetup { seed: 0, rules: config.rules, serve_rule: config.serve_rule };
+        let broadcaster = if config.broadcast.is_enabled() {
+            Some(Broadcaster::new(&config.broadcast))
+        } else {
+            None
+        };
+        let game = new_server_match(&setup, &broadcaster);
+        Server {
+            config: config,
+            transport: transport,
+            clients: ~[],
+            game: game,
+            broadcaster: broadcaster,
+            setup: setup,
+            history: std::vec::from_fn(STATE_HISTORY, |_| None),
+            match_id: 1,
+            tick: 0,
+            result_ticks: 0
+        }
+    }
+
+    fn player(&self, side: PaddleSide) -> Option<uint> {
+        self.clients.iter().position(|c| c.role == Player(side))
+    }
+
+    fn spectators(&self) -> uint {
+        self.clients.iter().count(|c| c.role == Spectator)
+    }
+
+    // runs one tick: takes in packets, advances the match if both players
+    // are there and sends everyone the new state
+    pub fn update(&mut self) {
+        self.receive();
+        self.drop_lost_clients();
+        match (self.player(LEFT), self.player(RIGHT)) {
+            (Some(left), Some(right)) if !self.game.world.resources.match_state.is_over() => {
+                self.game.world.resources.net_inputs[LEFT.index()] = self.clients[left].next_input();
+                self.game.world.resources.net_inputs[RIGHT.index()] = self.clients[right].next_input();
+                self.game.world.process(&InputSnapshot::none());
+                self.tick += 1;
+            },
+            (_, _) => {
+                for client in self.clients.mut_iter() {
+                    client.discard_inputs();
+                }
+                if self.game.world.resources.match_state.is_over() {
+                    self.result_ticks += 1;
+                    if self.result_ticks >= RESULT_TICKS {
+                        self.next_match();
+                    }
+                }
+            }
+        }
+        let values = Snapshot::capture(&self.game).values();
+        self.history[self.tick % STATE_HISTORY] = Some((self.tick, values.clone()));
+        for i in range(0, self.clients.len()) {
+            self.send_state(i, values.as_slice());
+        }
+        self.transport.flush();
+        match self.broadcaster {
+            Some(ref mut broadcaster) => broadcaster.release(),
+            None => ()
+        }
+    }
+
+    fn next_match(&mut self) {
+        self.game = new_server_match(&self.setup, &self.broadcaster);
+        self.history = std::vec::from_fn(STATE_HISTORY, |_| None);
+        self.match_id += 1;
+        self.tick = 0;
+        self.result_ticks = 0;
+        println!("server: starting match {}", self.match_id);
+    }
+
+    fn send_state(&mut self, i: uint, values: &[u64]) {
+        let base = match self.clients[i].acked {
+            Some((m, tick)) if m == self.match_id => match self.history[tick % STATE_HISTORY] {
+                Some((t, ref base)) if t == tick => Some((tick, base.clone())),
+                _ => None
+            },
+            _ => None
+        };
+        let (addr, seq) = (self.clients[i].addr, self.clients[i].next_seq - 1);
+        let packet = match base {
+            Some((tick, base)) => format!("STATE {} {} {} {} {:s}", self.match_id, self.tick, tick, seq,
+                                          encode_values(values, Some(base.as_slice()))),
+            None => format!("STATE {} {} - {} {:s}", self.match_id, self.tick, seq, encode_values(values, None))
+        };
+        self.transport.send(addr, packet);
+    }
+
+    fn receive(&mut self) {
+        loop {
+            let (line, from) = match self.transport.receive() {
+                Some(packet) => packet,
+                None => break
+            };
+            let words: ~[&str] = line.words().collect();
+            if words.len() == 3 && words[0] == "JOIN" {
+                match from_str::<u16>(words[1]) {
+                    Some(port) => self.join(SocketAddr { ip: from.ip, port: port }, words[2] == "play"),
+                    None => ()
+                }
+                continue;
+            }
+            if words.len() < 2 {
+                continue;
+            }
+            let addr = match from_str::<u16>(words[1]) {
+                Some(port) => SocketAddr { ip: from.ip, port: port },
+                None => continue
+            };
+            let i = match self.clients.iter().position(|c| c.addr == addr) {
+                Some(i) => i,
+                None => continue
+            };
+            let client = &mut self.clients[i];
+            client.last_heard = precise_time_ns();
+            let acked = if words.len() == 6 && words[0] == "INPUT" {
+                match from_str::<uint>(words[2]) {
+                    Some(first) => client.inputs_arrived(first, words[3]),
+                    None => ()
+                }
+                (from_str::<uint>(words[4]), from_str::<uint>(words[5]))
+            } else if words.len() == 4 && words[0] == "ACK" {
+                (from_str::<uint>(words[2]), from_str::<uint>(words[3]))
+            } else {
+                (None, None)
+            };
+            match acked {
+                (Some(m), Some(tick)) => client.acked = Some((m, tick)),
+                (_, _) => ()
+            }
+        }
+    }
+
+    fn join(&mut self, addr: SocketAddr, play: bool) {
+        let existing = self.clients.iter().find(|c| c.addr == addr).map(|c| c.role);
+        let role = match existing {
+            // a repeated JOIN, whose ACCEPT got lost
+            Some(role) => Some(role),
+            None => {
+                let role = match (play, self.player(LEFT), self.player(RIGHT)) {
+                    (true, None, _) => Some(Player(LEFT)),
+                    (true, _, None) => Some(Player(RIGHT)),
+                    (_, _, _) if self.spectators() < self.config.max_spectators => Some(Spectator),
+                    (_, _, _) => None
+                };
+                match role {
+                    Some(role) => {
+                        println!("server: {} joined as {:s}", addr.to_str(), role.name());
+                        self.clients.push(ServerClient {
+                            addr: addr,
+                            role: role,
+                            pending: ~[],
+                            next_seq: 1,
+                            last_input: NetInput::idle(),
+                            acked: None,
+                            last_heard: precise_time_ns()
+                        });
+                    },
+                    None => println!("server: {} turned away, the server is full", addr.to_str())
+                }
+                role
+            }
+        };
+        match role {
+            Some(role) => self.transport.send(addr, format!("ACCEPT {:s} {:s}", role.name(), self.setup.to_words())),
+            None => self.transport.send(addr, "FULL")
+        }
+    }
+
+    fn drop_lost_clients(&mut self) {
+        let now = precise_time_ns();
+        let (kept, lost) = std::util::replace(&mut self.clients, ~[]).partition(|c| now - c.last_heard < CLIENT_TIMEOUT_NS);
+        self.clients = kept;
+        for client in lost.iter() {
+            println!("server: {} ({:s}) is gone", client.addr.to_str(), client.role.name());
+        }
+    }
+}
+
+fn new_server_match(setup: &MatchSetup, broadcaster: &Option<Broadcaster>) -> MatchWorld {
+    let mut game = new_match_world(setup.rules, setup.serve_rule, ExplicitEuler, XorShiftRng::new());
+    add_net_inputs(&mut game);
+    match *broadcaster {
+        Some(ref broadcaster) => {
+            let bs = ~BroadcastSystem::new(broadcaster.sender(), *setup, game.world.entities.len());
+            game.world.global_systems.push(bs as ~GlobalSystem);
+        },
+        None => ()
+    }
+    game
+}
+
+static USAGE: &'static str = "usage: pong_server [--port PORT] [--spectators N] [--points N] [--games N] [--sets N]
+                   [--latency MS] [--loss FRACTION]
+                   [--broadcast ADDRESS:PORT] [--broadcast-delay SECONDS] [--record FILE]";
+
+pub fn run(args: &[~str]) {
+    let mut config = ServerConfig::new();
+    let mut i = 0;
+    while i < args.len() {
+        let value = if i + 1 < args.len() { Some(args[i + 1].as_slice()) } else { None };
+        match (args[i].as_slice(), value.and_then(|v| from_str::<uint>(v))) {
+            ("--port", _) if value.and_then(|v| from_str::<u16>(v)).is_some() => {
+                config.port = from_str(value.unwrap()).unwrap();
+            },
+            ("--spectators", Some(n)) => config.max_spectators = n,
+            ("--points", Some(n)) => config.rules.points_to_win = n,
+            ("--games", Some(n)) => config.rules.best_of_games = n,
+            ("--sets", Some(n)) => config.rules.best_of_sets = n,
+            ("--latency", Some(ms)) => config.conditions.latency_ms = ms as u64,
+            ("--loss", _) if value.and_then(|v| from_str::<f64>(v)).is_some() => {
+                config.conditions.loss = from_str(value.unwrap()).unwrap();
+            },
+            ("--broadcast", _) if value.is_some() => config.broadcast.address = Some(value.unwrap().to_owned()),
+            ("--broadcast-delay", _) if value.and_then(|v| from_str::<f64>(v)).is_some() => {
+                config.broadcast.delay_secs = from_str(value.unwrap()).unwrap();
+            },
+            ("--record", _) if value.is_some() => config.broadcast.record = Some(value.unwrap().to_owned()),
+            (_, _) => {
+                println!("{:s}", USAGE);
+                return;
+            }
+        }
+        i += 2;
+    }
+
+    let transport = match Transport::bind(config.port, config.conditions.clone()) {
+        Some(transport) => transport,
+        None => {
+            println!("server: can't listen on port {}", config.port);
+            return;
+        }
+    };
+    println!("server: listening on port {}", config.port);
+    let mut server = Server::new(config, transport);
+    let tick_ns = 1000000000/TICK_RATE;
+    let mut next_tick = precise_time_ns();
+    loop {
+        server.update();
+        next_tick += tick_ns;
+        let now = precise_time_ns();
+        if now < next_tick {
+            std::io::timer::sleep((next_tick - now)/1000000);
+        } else {
+            // too far behind to catch up, eg. after the machine was suspended
+            next_tick = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use server::*;
+
+    #[test]
+    fn full_values_survive_encoding() {
+        let values = ~[0u64, 7, 0xdeadbeef, 1 << 40];
+        let encoded = encode_values(values, None);
+        assert_eq!(decode_values(encoded, ~[]), Some(values));
+    }
+
+    #[test]
+    fn deltas_only_hold_changed_values() {
+        let base = ~[1u64, 2, 3];
+        let values = ~[1u64, 5, 3, 4];
+        let encoded = encode_values(values, Some(base.as_slice()));
+        assert_eq!(encoded, ~"1:5,3:4");
+        assert_eq!(decode_values(encoded, base.clone()), Some(values));
+        assert_eq!(encode_values(base, Some(base.as_slice())), ~"-");
+        assert_eq!(decode_values("-", base.clone()), Some(base));
+    }
+
+    #[test]
+    fn bad_encodings_are_refused() {
+        assert_eq!(decode_values("1:5:2", ~[1, 2]), None);
+        assert_eq!(decode_values("x:5", ~[1, 2]), None);
+        // past the end of the base, and not the next value either
+        assert_eq!(decode_values("3:5", ~[1, 2]), None);
+    }
+}
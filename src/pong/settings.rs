@@ -0,0 +1,59 @@
+// SETTINGS
+use ai::{BotAi,PredictorAi,BotLevel,Normal};
+use bindings::{Bindings,BINDINGS_FILE};
+use broadcast::Broadcaster;
+use extbot::BotLink;
+use gamepad::{Gamepads,GamepadConfig};
+use input::PADDLE_SPEED;
+use resources::*;
+
+// choices made in the settings menu, applied when a new match starts
+pub struct Settings {
+    mode: GameMode,
+    rules: MatchRules,
+    serve_rule: ServeRule,
+    // of local matches, netplay and server matches always use the default
+    integrator: Integrator,
+    // the first player's paddle follows the mouse instead of keys and gamepad
+    mouse_control: bool,
+    // top paddle speed under mouse control, in arena units per tick
+    mouse_speed: f64,
+    bot_ai: BotAi,
+    bot_level: BotLevel,
+    // bots print what they decide, for bots that decide anything
+    bot_log: bool,
+    // bots on these sides, indexed with PaddleSide::index, are run outside
+    // the game, see extbot.rs
+    external_bots: [Option<BotLink>, ..2],
+    // how long to wait for an external bot's move every tick
+    external_timeout_ms: u64,
+    bindings: @mut Bindings,
+    // polled by the main loop, read by the paddles of human players
+    gamepads: @mut Gamepads,
+    // every match is sent to this, see broadcast.rs
+    broadcaster: Option<@mut Broadcaster>,
+    // ticks run per frame during a local match, can be fractional
+    time_scale: f64
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            mode: HumanVsBot,
+            rules: MatchRules::new(),
+            serve_rule: ScorerServes,
+            integrator: ExplicitEuler,
+            mouse_control: false,
+            mouse_speed: PADDLE_SPEED,
+            bot_ai: PredictorAi,
+            bot_level: Normal,
+            bot_log: false,
+            external_bots: [None, None],
+            external_timeout_ms: 5,
+            bindings: @mut Bindings::load(BINDINGS_FILE),
+            gamepads: @mut Gamepads::new(GamepadConfig::new()),
+            broadcaster: None,
+            time_scale: 1.0
+        }
+    }
+}
@@ -0,0 +1,1260 @@
+// GAME STATES
+extern mod glfw;
+extern mod std;
+use ai::*;
+use bindings::*;
+use broadcast::*;
+use client::*;
+use console::{Command,CommandScope};
+use entities::*;
+use extbot::*;
+use game::*;
+use globalsystems::*;
+use graphics::RenderSystem;
+use input::*;
+// the action, as opposed to the Quit transition
+use QuitAction = input::Quit;
+use menu::*;
+use netplay::*;
+use resources::*;
+use server::{Player,Spectator};
+use settings::Settings;
+use systems::*;
+use world::World;
+use std::rand::{Rng,XorShiftRng};
+
+pub enum Transition {
+    Stay,
+    Push(@mut GameState),
+    Pop,
+    // replaces the topmost state
+    Switch(@mut GameState),
+    // replaces the whole stack
+    Reset(@mut GameState),
+    Quit
+}
+
+pub trait GameState {
+    // runs one tick, only called for the topmost state
+    fn update(&mut self, input: &InputSnapshot) -> Transition;
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition;
+
+    // actions triggered by something other than a key, eg. a gamepad button
+    fn action_pressed(&mut self, _: Action) -> Transition {
+        Stay
+    }
+
+    fn render(&self);
+
+    // overlays are drawn on top of the state below them, which is drawn but not updated
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    // console commands that only exist while the state does, see
+    // CommandScope
+    fn run_command(&mut self, _: &[&str]) -> Option<Result<~str, ~str>> {
+        None
+    }
+
+    fn command_help(&self) -> ~[~str] {
+        ~[]
+    }
+}
+
+pub struct StateStack {
+    states: ~[@mut GameState]
+}
+
+impl StateStack {
+    pub fn new(initial: @mut GameState) -> StateStack {
+        StateStack { states: ~[initial] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    fn top(&self) -> @mut GameState {
+        self.states[self.states.len() - 1]
+    }
+
+    fn apply(&mut self, transition: Transition) {
+        match transition {
+            Stay => (),
+            Push(state) => self.states.push(state),
+            Pop => {
+                self.states.pop();
+            },
+            Switch(state) => {
+                self.states.pop();
+                self.states.push(state);
+            },
+            Reset(state) => {
+                self.states.clear();
+                self.states.push(state);
+            },
+            Quit => self.states.clear()
+        }
+    }
+
+    pub fn update(&mut self, input: &InputSnapshot) {
+        if !self.is_empty() {
+            let transition = self.top().update(input);
+            self.apply(transition);
+        }
+    }
+
+    pub fn key_pressed(&mut self, key: glfw::Key) {
+        if !self.is_empty() {
+            let transition = self.top().key_pressed(key);
+            self.apply(transition);
+        }
+    }
+
+    // a key held down, only menu navigation repeats so that a held key
+    // doesn't pause over and over or bind itself when rebinding
+    pub fn key_repeated(&mut self, key: glfw::Key) {
+        match key {
+            glfw::KeyUp | glfw::KeyDown | glfw::KeyLeft | glfw::KeyRight => self.key_pressed(key),
+            _ => ()
+        }
+    }
+
+    pub fn action_pressed(&mut self, action: Action) {
+        if !self.is_empty() {
+            let transition = self.top().action_pressed(action);
+            self.apply(transition);
+        }
+    }
+
+    pub fn render(&self) {
+        if self.is_empty() {
+            return
+        }
+        // start from the topmost state that isn't an overlay
+        let mut first = self.states.len() - 1;
+        while first > 0 && self.states[first].is_overlay() {
+            first -= 1;
+        }
+        for state in self.states.slice_from(first).iter() {
+            state.render();
+        }
+    }
+}
+
+// the topmost state that knows a command runs it, eg. the match below the
+// pause menu
+impl CommandScope for StateStack {
+    fn run_command(&mut self, words: &[&str]) -> Option<Result<~str, ~str>> {
+        for state in self.states.iter().invert() {
+            match state.run_command(words) {
+                Some(result) => return Some(result),
+                None => ()
+            }
+        }
+        None
+    }
+
+    fn command_help(&self) -> ~[~str] {
+        let mut lines = ~[];
+        for state in self.states.iter().invert() {
+            lines.push_all_move(state.command_help());
+        }
+        lines
+    }
+}
+
+// a world for a menu, optionally over a darkened state below it, see Menu::new
+fn new_menu_world(title: &str, overlay: bool) -> World {
+    let mut world = World::new();
+    if overlay {
+        world.add(new_overlay());
+    } else {
+        world.add(new_background());
+    }
+    world.add(new_text(2.0, 2.4, 0.25, title, [1.0, 1.0, 1.0, 0.0]));
+    world.systems.push(~TweenSystem as ~System);
+    world
+}
+
+fn set_text(world: &mut World, entity: uint, text: ~str) {
+    world.entities[entity].text.get_mut_ref().text = text;
+}
+
+pub struct TitleState {
+    world: World,
+    menu: Menu,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl TitleState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings) -> TitleState {
+        let mut world = new_menu_world("PONG", false);
+        let menu = Menu::new(&mut world, [~"PLAY", ~"SETTINGS", ~"QUIT"], 2.0, 1.6);
+        TitleState {
+            world: world,
+            menu: menu,
+            rs: rs,
+            settings: settings
+        }
+    }
+}
+
+impl GameState for TitleState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        self.world.process(input);
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        match self.menu.key_pressed(&mut self.world, key) {
+            MenuSelect(0) => Switch(@mut PlayingState::new(self.rs, self.settings) as @mut GameState),
+            MenuSelect(1) => Push(@mut SettingsState::new(self.rs, self.settings) as @mut GameState),
+            MenuSelect(_) | MenuBack => Quit,
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.world);
+    }
+}
+
+pub struct PlayingState {
+    game: MatchWorld,
+    // the GameOverState has been pushed
+    game_over: bool,
+    // ticks owed to the world, see Settings::time_scale
+    ticks_due: f64,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl PlayingState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings) -> PlayingState {
+        let mut game = new_match_world(settings.rules, settings.serve_rule, settings.integrator, XorShiftRng::new());
+        let match_entities = game.world.entities.len();
+        let ball = game.ball;
+
+        // each paddle gets its input from the player's keys and gamepad or a bot
+        // depending on the mode
+        for &side in [LEFT, RIGHT].iter() {
+            let (paddle, opponent) = (game.paddle(side), game.paddle(side.opponent()));
+            if settings.mode.is_human(side) {
+                game.world.resources.serve.human_sides.push(side);
+                let player = side.index();
+                // the mouse always pulls towards the cursor, so it can't be
+                // combined with the other sources
+                let sources = if settings.mouse_control && player == 0 {
+                    ~[~MouseSource { max_speed: settings.mouse_speed } as ~InputSource]
+                } else {
+                    ~[~KeyboardSource { player: player } as ~InputSource,
+                      ~GamepadSource { player: player } as ~InputSource]
+                };
+                let pis = ~PlayerInputSystem { paddle: paddle, side: side, sources: sources };
+                game.world.global_systems.push(pis as ~GlobalSystem);
+            } else {
+                game.world.resources.bot_difficulty[side.index()] = settings.bot_level.difficulty();
+                let bot = BotInputSystem::new(side, paddle, ball, settings.bot_ai.strategy(paddle, opponent, settings.bot_log));
+                match settings.external_bots[side.index()] {
+                    Some(ref link) => {
+                        let ext = ExternalBot::connect(link.clone(), settings.external_timeout_ms);
+                        let ebs = ~ExternalBotSystem::new(side, paddle, opponent, ball, ext, bot);
+                        game.world.global_systems.push(ebs as ~GlobalSystem);
+                    },
+                    None => game.world.global_systems.push(~bot as ~GlobalSystem)
+                }
+            }
+        }
+
+        match settings.broadcaster {
+            Some(broadcaster) => {
+                let setup = MatchSetup { seed: 0, rules: settings.rules, serve_rule: settings.serve_rule };
+                let bs = ~BroadcastSystem::new(broadcaster.sender(), setup, match_entities);
+                game.world.global_systems.push(bs as ~GlobalSystem);
+            },
+            None => ()
+        }
+
+        PlayingState {
+            game: game,
+            game_over: false,
+            ticks_due: 0.0,
+            rs: rs,
+            settings: settings
+        }
+    }
+
+    fn set(&mut self, name: &str, value: Option<&str>) -> Result<~str, ~str> {
+        let setting = match BallSetting::from_name(name) {
+            Some(setting) => setting,
+            None => return Err(format!("no variable {:s}", name))
+        };
+        match value {
+            None => (),
+            Some(value) => match from_str::<f64>(value) {
+                Some(value) if value >= 0.0 => self.game.set_ball_setting(setting, value),
+                _ => return Err(format!("bad value {:s}", value))
+            }
+        }
+        Ok(format!("{:s} is {}", name, self.game.ball_setting(setting)))
+    }
+}
+
+impl GameState for PlayingState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        self.ticks_due += self.settings.time_scale;
+        while self.ticks_due >= 1.0 {
+            self.game.world.process(input);
+            self.ticks_due -= 1.0;
+        }
+        match self.game.world.resources.match_state.winner {
+            Some(winner) if !self.game_over => {
+                self.game_over = true;
+                Push(@mut GameOverState::new(self.rs, self.settings, winner) as @mut GameState)
+            },
+            _ => Stay
+        }
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        let bindings = self.settings.bindings;
+        if bindings.is_bound(key, QuitAction) {
+            self.action_pressed(QuitAction)
+        } else if bindings.is_bound(key, Pause) {
+            self.action_pressed(Pause)
+        } else {
+            Stay
+        }
+    }
+
+    fn action_pressed(&mut self, action: Action) -> Transition {
+        match action {
+            QuitAction => Quit,
+            Pause => Push(@mut PausedState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.game.world);
+    }
+
+    // the commands only exist until the match is over
+    fn run_command(&mut self, words: &[&str]) -> Option<Result<~str, ~str>> {
+        if self.game.world.resources.match_state.winner.is_some() {
+            return None;
+        }
+        Some(match words {
+            ["spawn", "ball"] => {
+                self.game.spawn_ball();
+                Ok(~"spawned a ball")
+            },
+            ["spawn", ..] => Err(~"only balls can be spawned"),
+            ["score", side, points] => {
+                let side = match side {
+                    "left" => LEFT,
+                    "right" => RIGHT,
+                    _ => return Some(Err(format!("no side {:s}", side)))
+                };
+                match from_str::<uint>(points) {
+                    Some(points) => {
+                        self.game.set_points(side, points);
+                        let points = self.game.world.resources.match_state.points;
+                        Ok(format!("{} - {}", points[0], points[1]))
+                    },
+                    None => Err(format!("bad points {:s}", points))
+                }
+            },
+            ["score", ..] => Err(~"usage: score left|right POINTS"),
+            // for this match's bots and the next match
+            ["bot", "difficulty", name] => match BotLevel::from_name(name) {
+                Some(level) => {
+                    self.settings.bot_level = level;
+                    for &side in [LEFT, RIGHT].iter() {
+                        if !self.settings.mode.is_human(side) {
+                            self.game.world.resources.bot_difficulty[side.index()] = level.difficulty();
+                        }
+                    }
+                    Ok(format!("bot difficulty is {:s}", name))
+                },
+                None => Err(format!("no difficulty {:s}, expected classic, easy, normal or hard", name))
+            },
+            ["bot", ..] => Err(~"usage: bot difficulty classic|easy|normal|hard"),
+            ["set"] => Ok(BALL_SETTINGS.iter().map(|&s| format!("{:s} = {}", s.name(), self.game.ball_setting(s)))
+                                       .collect::<~[~str]>().connect("\n")),
+            ["set", name] => self.set(name, None),
+            ["set", name, value] => self.set(name, Some(value)),
+            ["set", ..] => Err(~"usage: set [VARIABLE [VALUE]]"),
+            _ => return None
+        })
+    }
+
+    fn command_help(&self) -> ~[~str] {
+        let mut lines = ~[~"spawn ball", ~"score left|right POINTS", ~"bot difficulty classic|easy|normal|hard",
+                          ~"set [VARIABLE [VALUE]]"];
+        for s in BALL_SETTINGS.iter() {
+            lines.push(format!("set {:s} VALUE", s.name()));
+        }
+        lines
+    }
+}
+
+// `timescale 0.25` in the console slows local matches down, netplay and
+// server matches keep their pace
+pub struct TimescaleCommand {
+    settings: @mut Settings
+}
+
+impl Command for TimescaleCommand {
+    fn run(&self, args: &[&str]) -> Result<~str, ~str> {
+        match args {
+            [] => Ok(format!("timescale is {}", self.settings.time_scale)),
+            [scale] => match from_str::<f64>(scale) {
+                Some(scale) if scale >= 0.0 && scale <= 8.0 => {
+                    self.settings.time_scale = scale;
+                    Ok(format!("timescale is {}", scale))
+                },
+                _ => Err(format!("bad timescale {:s}, expected 0 to 8", scale))
+            },
+            _ => Err(~"usage: timescale [SCALE]")
+        }
+    }
+}
+
+pub struct PausedState {
+    world: World,
+    menu: Menu,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl PausedState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings) -> PausedState {
+        let mut world = new_menu_world("PAUSED", true);
+        let menu = Menu::new(&mut world, [~"RESUME", ~"SETTINGS", ~"QUIT TO TITLE"], 2.0, 1.6);
+        PausedState {
+            world: world,
+            menu: menu,
+            rs: rs,
+            settings: settings
+        }
+    }
+}
+
+impl GameState for PausedState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        self.world.process(input);
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        if self.settings.bindings.is_bound(key, Pause) {
+            return Pop;
+        }
+        match (key, self.menu.key_pressed(&mut self.world, key)) {
+            (_, MenuBack) | (_, MenuSelect(0)) => Pop,
+            // changed rules only apply from the next match on
+            (_, MenuSelect(1)) => Push(@mut SettingsState::new(self.rs, self.settings) as @mut GameState),
+            (_, MenuSelect(_)) => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            (_, _) => Stay
+        }
+    }
+
+    fn action_pressed(&mut self, action: Action) -> Transition {
+        match action {
+            Pause => Pop,
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.world);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+pub struct SettingsState {
+    world: World,
+    menu: Menu,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+fn on_off(b: bool) -> &'static str {
+    if b { "ON" } else { "OFF" }
+}
+
+// steps `value` through `choices` in direction `delta`, wrapping around
+fn cycle<T: Eq + Clone>(choices: &[T], value: T, delta: int) -> T {
+    let len = choices.len() as int;
+    let i = match choices.iter().position(|c| *c == value) {
+        Some(i) => i as int,
+        None => 0
+    };
+    choices[(((i + delta) % len + len) % len) as uint].clone()
+}
+
+impl SettingsState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings) -> SettingsState {
+        let mut world = new_menu_world("SETTINGS", false);
+        let menu = Menu::new(&mut world, [~"", ~"", ~"", ~"", ~"", ~"", ~"", ~"", ~"", ~"", ~"", ~"", ~"CONTROLS", ~"BACK"], 2.0, 2.05);
+        let mut state = SettingsState {
+            world: world,
+            menu: menu,
+            rs: rs,
+            settings: settings
+        };
+        state.update_labels();
+        state
+    }
+
+    fn update_labels(&mut self) {
+        let s = &*self.settings;
+        self.menu.set_label(&mut self.world, 0, s.mode.label().to_owned());
+        self.menu.set_label(&mut self.world, 1, format!("POINTS TO WIN {}", s.rules.points_to_win));
+        self.menu.set_label(&mut self.world, 2, format!("WIN BY TWO {}", on_off(s.rules.win_by_two)));
+        self.menu.set_label(&mut self.world, 3, format!("BEST OF {} GAMES", s.rules.best_of_games));
+        self.menu.set_label(&mut self.world, 4, format!("BEST OF {} SETS", s.rules.best_of_sets));
+        self.menu.set_label(&mut self.world, 5, match s.rules.time_limit {
+            Some(ticks) => format!("TIME LIMIT {} MIN", ticks/3600),
+            None => ~"TIME LIMIT OFF"
+        });
+        self.menu.set_label(&mut self.world, 6, match s.serve_rule {
+            ScorerServes => ~"SCORER SERVES",
+            ConcederServes => ~"CONCEDER SERVES"
+        });
+        self.menu.set_label(&mut self.world, 7, format!("MOUSE CONTROL {}", on_off(s.mouse_control)));
+        self.menu.set_label(&mut self.world, 8, format!("MOUSE SPEED {:.1f}", s.mouse_speed*60.0));
+        self.menu.set_label(&mut self.world, 9, format!("BOT AI {}", s.bot_ai.label()));
+        self.menu.set_label(&mut self.world, 10, format!("BOT LEVEL {}", s.bot_level.label()));
+        self.menu.set_label(&mut self.world, 11, format!("PHYSICS {}", s.integrator.label()));
+    }
+
+    fn adjust(&mut self, item: uint, delta: int) {
+        {
+            let s = &mut *self.settings;
+            match item {
+                0 => s.mode = cycle(GAME_MODES, s.mode, delta),
+                1 => s.rules.points_to_win = cycle([5u, 11, 21], s.rules.points_to_win, delta),
+                2 => s.rules.win_by_two = !s.rules.win_by_two,
+                3 => s.rules.best_of_games = cycle([1u, 3, 5, 7], s.rules.best_of_games, delta),
+                4 => s.rules.best_of_sets = cycle([1u, 3, 5], s.rules.best_of_sets, delta),
+                5 => s.rules.time_limit = cycle([None, Some(2*3600u), Some(5*3600u), Some(10*3600u)], s.rules.time_limit, delta),
+                6 => s.serve_rule = match s.serve_rule {
+                    ScorerServes => ConcederServes,
+                    ConcederServes => ScorerServes
+                },
+                7 => s.mouse_control = !s.mouse_control,
+                8 => s.mouse_speed = cycle([1.0/60.0, PADDLE_SPEED, 2.0/60.0, 3.0/60.0], s.mouse_speed, delta),
+                9 => s.bot_ai = cycle(BOT_AIS, s.bot_ai, delta),
+                10 => s.bot_level = cycle(BOT_LEVELS, s.bot_level, delta),
+                11 => s.integrator = cycle(INTEGRATORS, s.integrator, delta),
+                _ => ()
+            }
+        }
+        self.update_labels();
+    }
+}
+
+impl GameState for SettingsState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        self.world.process(input);
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        match self.menu.key_pressed(&mut self.world, key) {
+            MenuAdjust(item, delta) => {
+                self.adjust(item, delta);
+                Stay
+            },
+            MenuSelect(12) => Push(@mut ControlsState::new(self.rs, self.settings.bindings) as @mut GameState),
+            MenuSelect(13) | MenuBack => Pop,
+            MenuSelect(item) => {
+                self.adjust(item, 1);
+                Stay
+            },
+            MenuNone => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.world);
+    }
+}
+
+// rebinding keys, changes are saved to the bindings file when leaving
+pub struct ControlsState {
+    world: World,
+    menu: Menu,
+    bindings: @mut Bindings,
+    // (player, action) pairs in menu order
+    controls: ~[(uint, Action)],
+    // the control waiting for a key to be pressed
+    rebinding: Option<(uint, Action)>,
+    rs: @RenderSystem
+}
+
+impl ControlsState {
+    pub fn new(rs: @RenderSystem, bindings: @mut Bindings) -> ControlsState {
+        let mut controls = ~[];
+        for player in range(0, PLAYERS) {
+            for action in ACTIONS.iter() {
+                if player == 0 || action.is_per_player() {
+                    controls.push((player, *action));
+                }
+            }
+        }
+        let mut labels: ~[~str] = controls.iter().map(|_| ~"").collect();
+        labels.push(~"RESET DEFAULTS");
+        labels.push(~"BACK");
+        let mut world = new_menu_world("CONTROLS", false);
+        let menu = Menu::new(&mut world, labels, 2.0, 2.05);
+        let mut state = ControlsState {
+            world: world,
+            menu: menu,
+            bindings: bindings,
+            controls: controls,
+            rebinding: None,
+            rs: rs
+        };
+        state.update_labels();
+        state
+    }
+
+    fn update_labels(&mut self) {
+        for (i, &(player, action)) in self.controls.iter().enumerate() {
+            let keys = match self.rebinding {
+                Some((p, a)) if p == player && a == action => ~"PRESS A KEY",
+                _ => self.bindings.describe(player, action)
+            };
+            let label = if action.is_per_player() {
+                format!("P{} {:s}: {:s}", player + 1, action.label(), keys)
+            } else {
+                format!("{:s}: {:s}", action.label(), keys)
+            };
+            self.menu.set_label(&mut self.world, i, label);
+        }
+    }
+}
+
+impl GameState for ControlsState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        self.world.process(input);
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        match self.rebinding {
+            Some((player, action)) => {
+                // escape cancels, any other key is added to the action,
+                // unless it has no name to save it under
+                if key != glfw::KeyEscape && key_name(key).is_some() {
+                    self.bindings.bind(player, action, key);
+                }
+                self.rebinding = None;
+                self.update_labels();
+                return Stay;
+            },
+            None => ()
+        }
+        if key == glfw::KeyDelete && self.menu.selected < self.controls.len() {
+            let (player, action) = self.controls[self.menu.selected];
+            self.bindings.clear(player, action);
+            self.update_labels();
+            return Stay;
+        }
+        match self.menu.key_pressed(&mut self.world, key) {
+            MenuSelect(i) if i < self.controls.len() => {
+                self.rebinding = Some(self.controls[i]);
+                self.update_labels();
+                Stay
+            },
+            MenuSelect(i) if i == self.controls.len() => {
+                *self.bindings = Bindings::default();
+                self.update_labels();
+                Stay
+            },
+            MenuSelect(_) | MenuBack => {
+                self.bindings.save(BINDINGS_FILE);
+                Pop
+            },
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.world);
+    }
+}
+
+pub struct GameOverState {
+    world: World,
+    menu: Menu,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl GameOverState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings, winner: PaddleSide) -> GameOverState {
+        let title = match winner {
+            LEFT => "LEFT WINS",
+            RIGHT => "RIGHT WINS"
+        };
+        let mut world = new_menu_world(title, true);
+        let menu = Menu::new(&mut world, [~"PLAY AGAIN", ~"TITLE", ~"QUIT"], 2.0, 1.6);
+        GameOverState {
+            world: world,
+            menu: menu,
+            rs: rs,
+            settings: settings
+        }
+    }
+}
+
+impl GameState for GameOverState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        self.world.process(input);
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        match self.menu.key_pressed(&mut self.world, key) {
+            MenuSelect(0) => Reset(@mut PlayingState::new(self.rs, self.settings) as @mut GameState),
+            MenuSelect(1) | MenuBack => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            MenuSelect(_) => Quit,
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.world);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+// waits for the other player of a netplay match, see netplay.rs
+pub struct LobbyState {
+    world: World,
+    menu: Menu,
+    lobby: Lobby,
+    input_delay: uint,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl LobbyState {
+    // a host plays by its own settings, a joining peer by the host's
+    pub fn new(rs: @RenderSystem, settings: @mut Settings, config: NetplayConfig) -> LobbyState {
+        let mut world = new_menu_world("NETPLAY", false);
+        let menu = Menu::new(&mut world, [~"CANCEL"], 2.0, 1.0);
+        let setup = MatchSetup { seed: XorShiftRng::new().gen(), rules: settings.rules, serve_rule: settings.serve_rule };
+        let lobby = Lobby::new(config.clone(), setup);
+        let status = if !lobby.is_listening() {
+            format!("CAN'T LISTEN ON PORT {}", config.local_port)
+        } else {
+            match config.role {
+                Host => format!("WAITING FOR A PLAYER ON PORT {}", config.local_port),
+                Join(host) => format!("CONNECTING TO {}", host.to_str())
+            }
+        };
+        world.add(new_text(2.0, 1.6, 0.07, status, [1.0, 1.0, 1.0, 0.0]));
+        LobbyState {
+            world: world,
+            menu: menu,
+            lobby: lobby,
+            input_delay: config.input_delay,
+            rs: rs,
+            settings: settings
+        }
+    }
+}
+
+impl GameState for LobbyState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        self.world.process(input);
+        match self.lobby.poll() {
+            Some(connection) => Switch(@mut NetplayState::new(self.rs, self.settings, connection, self.input_delay) as @mut GameState),
+            None => Stay
+        }
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        match self.menu.key_pressed(&mut self.world, key) {
+            MenuSelect(_) | MenuBack => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.world);
+    }
+}
+
+// a match against another player over the network. It can't be paused, and
+// after it's over the session keeps running until the player leaves so that
+// the peer gets the last inputs too.
+pub struct NetplayState {
+    session: RollbackSession,
+    sources: ~[~InputSource],
+    status: uint,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl NetplayState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings, connection: Connection, input_delay: uint) -> NetplayState {
+        let mut session = RollbackSession::new(connection, input_delay);
+        let status = session.game.world.add(new_text(2.0, 0.15, 0.05, "", [1.0, 1.0, 1.0, 0.0]));
+        // the local player uses the first player's controls whichever side
+        // they're on
+        let sources = if settings.mouse_control {
+            ~[~MouseSource { max_speed: settings.mouse_speed } as ~InputSource]
+        } else {
+            ~[~KeyboardSource { player: 0 } as ~InputSource,
+              ~GamepadSource { player: 0 } as ~InputSource]
+        };
+        NetplayState {
+            session: session,
+            sources: sources,
+            status: status,
+            rs: rs,
+            settings: settings
+        }
+    }
+}
+
+impl GameState for NetplayState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        let serve = self.sources.iter().any(|s| s.is_held(input, Serve));
+        let paddle = self.session.game.paddle(self.session.local_side);
+        let y = self.session.game.world.entities[paddle].position.unwrap().y;
+        self.session.update(NetInput::from_velocity(combined_velocity(self.sources, input, y), serve));
+        let leave = self.settings.bindings.describe(0, QuitAction);
+        let status = if self.session.peer_lost() {
+            format!("CONNECTION LOST, {:s} TO LEAVE", leave)
+        } else {
+            match self.session.winner() {
+                Some(LEFT) => format!("LEFT WINS, {:s} TO LEAVE", leave),
+                Some(RIGHT) => format!("RIGHT WINS, {:s} TO LEAVE", leave),
+                None if self.session.is_desynced() => ~"DESYNC",
+                None if self.session.is_waiting() => ~"WAITING FOR THE OTHER PLAYER",
+                None => ~""
+            }
+        };
+        set_text(&mut self.session.game.world, self.status, status);
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        if self.settings.bindings.is_bound(key, QuitAction) {
+            self.action_pressed(QuitAction)
+        } else {
+            Stay
+        }
+    }
+
+    fn action_pressed(&mut self, action: Action) -> Transition {
+        match action {
+            QuitAction => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.session.game.world);
+    }
+}
+
+// a match run by a dedicated server, see server.rs and client.rs
+pub struct ClientState {
+    client: RemoteMatch,
+    // shown until the server has let us in
+    lobby: World,
+    menu: Menu,
+    // in the lobby, then in the match once it's showing
+    status: uint,
+    // the match world has been set up for rendering
+    showing: bool,
+    sources: ~[~InputSource],
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl ClientState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings, config: ClientConfig) -> ClientState {
+        let mut lobby = new_menu_world("SERVER", false);
+        let menu = Menu::new(&mut lobby, [~"CANCEL"], 2.0, 1.0);
+        let status = lobby.add(new_text(2.0, 0.15, 0.05, "", [1.0, 1.0, 1.0, 0.0]));
+        ClientState {
+            client: RemoteMatch::new(config),
+            lobby: lobby,
+            menu: menu,
+            status: status,
+            showing: false,
+            // the mouse isn't supported, the paddle isn't there until the
+            // server has let us in
+            sources: ~[~KeyboardSource { player: 0 } as ~InputSource,
+                       ~GamepadSource { player: 0 } as ~InputSource],
+            rs: rs,
+            settings: settings
+        }
+    }
+
+    fn status_text(&self) -> ~str {
+        let leave = self.settings.bindings.describe(0, QuitAction);
+        let c = &self.client;
+        if !c.is_listening() {
+            format!("CAN'T LISTEN ON PORT {}", c.config.local_port)
+        } else if c.full {
+            ~"THE SERVER IS FULL"
+        } else if c.server_lost() {
+            format!("LOST THE SERVER, {:s} TO LEAVE", leave)
+        } else {
+            match (c.role, c.winner()) {
+                (None, _) => format!("CONNECTING TO {}", c.config.server.to_str()),
+                (Some(_), Some(LEFT)) => ~"LEFT WINS",
+                (Some(_), Some(RIGHT)) => ~"RIGHT WINS",
+                (Some(Spectator), None) => ~"WATCHING",
+                (Some(Player(_)), None) => ~""
+            }
+        }
+    }
+}
+
+impl GameState for ClientState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        if self.showing {
+            let serve = self.sources.iter().any(|s| s.is_held(input, Serve));
+            let y = match (&self.client.view, self.client.role) {
+                (&Some(ref view), Some(Player(side))) => view.game.world.entities[view.game.paddle(side)].position.unwrap().y,
+                (_, _) => 0.0
+            };
+            self.client.update(NetInput::from_velocity(combined_velocity(self.sources, input, y), serve));
+        } else {
+            self.lobby.process(input);
+            self.client.update(NetInput::idle());
+            match self.client.view {
+                Some(ref mut view) => {
+                    self.status = view.game.world.add(new_text(2.0, 0.15, 0.05, "", [1.0, 1.0, 1.0, 0.0]));
+                    self.showing = true;
+                },
+                None => ()
+            }
+        }
+        let status = self.status_text();
+        match self.client.view {
+            Some(ref mut view) if self.showing => set_text(&mut view.game.world, self.status, status),
+            _ => set_text(&mut self.lobby, self.status, status)
+        }
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        if self.settings.bindings.is_bound(key, QuitAction) {
+            return self.action_pressed(QuitAction);
+        }
+        if self.showing {
+            return Stay;
+        }
+        match self.menu.key_pressed(&mut self.lobby, key) {
+            MenuSelect(_) | MenuBack => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn action_pressed(&mut self, action: Action) -> Transition {
+        match action {
+            QuitAction => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        match self.client.view {
+            Some(ref view) if self.showing => self.rs.render(&view.game.world),
+            _ => self.rs.render(&self.lobby)
+        }
+    }
+}
+
+// shows a match broadcast from elsewhere, see broadcast.rs
+pub struct WatchState {
+    lines: Port<~str>,
+    reader: StreamReader,
+    view: Option<MatchView>,
+    // the reader's match the view was set up for
+    view_match: uint,
+    // shown until the first keyframe
+    lobby: World,
+    menu: Menu,
+    // in the lobby, then in the view once there is one
+    status: uint,
+    address: ~str,
+    ended: bool,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl WatchState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings, address: ~str) -> WatchState {
+        let mut lobby = new_menu_world("BROADCAST", false);
+        let menu = Menu::new(&mut lobby, [~"CANCEL"], 2.0, 1.0);
+        let status = lobby.add(new_text(2.0, 0.15, 0.05, "", [1.0, 1.0, 1.0, 0.0]));
+        WatchState {
+            lines: watch(address.clone()),
+            reader: StreamReader::new(),
+            view: None,
+            view_match: 0,
+            lobby: lobby,
+            menu: menu,
+            status: status,
+            address: address,
+            ended: false,
+            rs: rs,
+            settings: settings
+        }
+    }
+}
+
+impl GameState for WatchState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        loop {
+            match self.lines.try_recv() {
+                Some(line) => if line.is_empty() {
+                    self.ended = true;
+                } else {
+                    self.reader.read(line);
+                },
+                None => break
+            }
+        }
+        if self.reader.matches != self.view_match {
+            let mut view = MatchView::new(self.reader.setup.get_ref());
+            self.status = view.game.world.add(new_text(2.0, 0.15, 0.05, "", [1.0, 1.0, 1.0, 0.0]));
+            self.view = Some(view);
+            self.view_match = self.reader.matches;
+        }
+        match (&mut self.view, &self.reader.values) {
+            (&Some(ref mut view), &Some(ref values)) => {
+                view.show(values.as_slice());
+            },
+            (_, _) => self.lobby.process(input)
+        }
+        let status = match (self.ended, &self.view) {
+            (true, _) => format!("THE BROADCAST HAS ENDED, {:s} TO LEAVE", self.settings.bindings.describe(0, QuitAction)),
+            (false, &None) => format!("WAITING FOR {:s}", self.address),
+            (false, &Some(_)) => ~""
+        };
+        match self.view {
+            Some(ref mut view) => set_text(&mut view.game.world, self.status, status),
+            None => set_text(&mut self.lobby, self.status, status)
+        }
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        if self.settings.bindings.is_bound(key, QuitAction) {
+            return self.action_pressed(QuitAction);
+        }
+        if self.view.is_some() {
+            return Stay;
+        }
+        match self.menu.key_pressed(&mut self.lobby, key) {
+            MenuSelect(_) | MenuBack => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn action_pressed(&mut self, action: Action) -> Transition {
+        match action {
+            QuitAction => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        match self.view {
+            Some(ref view) => self.rs.render(&view.game.world),
+            None => self.rs.render(&self.lobby)
+        }
+    }
+}
+
+// playback speeds, in ticks per frame
+static REPLAY_SPEEDS: [f64, ..7] = [0.125, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+// how far page up and page down seek, in ticks
+static REPLAY_SEEK_TICKS: uint = 300;
+// the timeline along the bottom of the arena
+static TIMELINE_X: f64 = 2.0;
+static TIMELINE_Y: f64 = 0.06;
+static TIMELINE_WIDTH: f64 = 3.6;
+static TIMELINE_HEIGHT: f64 = 0.04;
+
+// minutes and seconds
+fn clock(ticks: uint) -> ~str {
+    let secs = ticks/60;
+    format!("{}:{:02u}", secs/60, secs%60)
+}
+
+// plays back a recording, see broadcast::Recording
+pub struct ReplayState {
+    recording: Recording,
+    view: MatchView,
+    // the recording's match the view was set up for
+    view_match: uint,
+    // in ticks, fractional when playing slower than normal
+    position: f64,
+    // the tick the view shows
+    shown: Option<uint>,
+    playing: bool,
+    // index into REPLAY_SPEEDS
+    speed: uint,
+    // entities of the view, added again with every match
+    progress: uint,
+    status: uint,
+    rs: @RenderSystem,
+    settings: @mut Settings
+}
+
+impl ReplayState {
+    pub fn new(rs: @RenderSystem, settings: @mut Settings, recording: Recording) -> ReplayState {
+        let view = MatchView::new(&recording.setups[0]);
+        let mut state = ReplayState {
+            recording: recording,
+            view: view,
+            view_match: 0,
+            position: 0.0,
+            shown: None,
+            playing: true,
+            speed: 3,
+            progress: 0,
+            status: 0,
+            rs: rs,
+            settings: settings
+        };
+        state.add_overlay();
+        state
+    }
+
+    fn add_overlay(&mut self) {
+        let world = &mut self.view.game.world;
+        world.add(new_rect(TIMELINE_X, TIMELINE_Y, TIMELINE_WIDTH, TIMELINE_HEIGHT, [0.0, 0.0, 0.0, 0.5]));
+        self.progress = world.add(new_rect(TIMELINE_X, TIMELINE_Y, 0.0, TIMELINE_HEIGHT, [1.0, 1.0, 1.0, 1.0]));
+        self.status = world.add(new_text(2.0, 0.2, 0.05, "", [1.0, 1.0, 1.0, 0.0]));
+        world.add(new_text(2.0, 2.92, 0.035, "SPACE PLAY  LEFT RIGHT STEP  PGUP PGDN SEEK  UP DOWN SPEED  H HITBOXES  V VELOCITIES", [1.0, 1.0, 1.0, 0.0]));
+    }
+
+    fn last_tick(&self) -> uint {
+        self.recording.len() - 1
+    }
+
+    fn seek(&mut self, tick: uint) {
+        self.position = tick.min(&self.last_tick()) as f64;
+    }
+
+    // puts the current tick into the view
+    fn show(&mut self) {
+        let tick = self.position as uint;
+        let m = self.recording.match_at(tick);
+        if m != self.view_match {
+            self.view = MatchView::new(&self.recording.setups[m]);
+            self.view_match = m;
+            self.shown = None;
+            self.add_overlay();
+        }
+        if self.shown != Some(tick) {
+            self.view.show(self.recording.values_at(tick));
+            self.shown = Some(tick);
+        }
+        let last = self.last_tick();
+        let fraction = if last == 0 { 1.0 } else { tick as f64/last as f64 };
+        let width = TIMELINE_WIDTH*fraction;
+        {
+            let progress = &mut self.view.game.world.entities[self.progress];
+            progress.sprite.get_mut_ref().x_size = width;
+            progress.position.get_mut_ref().x = TIMELINE_X - (TIMELINE_WIDTH - width)/2.0;
+        }
+        let status = format!("{:s} {}X  {:s} / {:s}  MATCH {} OF {}",
+            if self.playing { "PLAYING" } else { "PAUSED" }, REPLAY_SPEEDS[self.speed],
+            clock(tick), clock(last), m + 1, self.recording.setups.len());
+        set_text(&mut self.view.game.world, self.status, status);
+    }
+}
+
+impl GameState for ReplayState {
+    fn update(&mut self, input: &InputSnapshot) -> Transition {
+        // clicking or dragging on the timeline seeks
+        if input.mouse_down {
+            let (x, y) = screen_to_world(input.cursor, input.window_size);
+            let from = TIMELINE_X - TIMELINE_WIDTH/2.0;
+            if (y - TIMELINE_Y).abs() < 0.1 && x >= from && x <= from + TIMELINE_WIDTH {
+                self.position = (x - from)/TIMELINE_WIDTH*self.last_tick() as f64;
+            }
+        }
+        if self.playing {
+            let last = self.last_tick() as f64;
+            self.position = (self.position + REPLAY_SPEEDS[self.speed]).min(&last);
+            if self.position >= last {
+                self.playing = false;
+            }
+        }
+        self.show();
+        Stay
+    }
+
+    fn key_pressed(&mut self, key: glfw::Key) -> Transition {
+        if self.settings.bindings.is_bound(key, QuitAction) {
+            return self.action_pressed(QuitAction);
+        }
+        let tick = self.position as uint;
+        match key {
+            glfw::KeySpace => {
+                // playing from the end starts over
+                if !self.playing && tick == self.last_tick() {
+                    self.position = 0.0;
+                }
+                self.playing = !self.playing;
+            },
+            glfw::KeyRight => {
+                self.playing = false;
+                self.seek(tick + 1);
+            },
+            glfw::KeyLeft => {
+                self.playing = false;
+                self.seek(if tick > 0 { tick - 1 } else { 0 });
+            },
+            glfw::KeyPageDown => self.seek(tick + REPLAY_SEEK_TICKS),
+            glfw::KeyPageUp => self.seek(tick - tick.min(&REPLAY_SEEK_TICKS)),
+            glfw::KeyHome => self.seek(0),
+            glfw::KeyEnd => self.seek(self.last_tick()),
+            glfw::KeyUp => self.speed = (self.speed + 1).min(&(REPLAY_SPEEDS.len() - 1)),
+            glfw::KeyDown => if self.speed > 0 { self.speed -= 1 },
+            glfw::KeyH => self.rs.debug.hitboxes = !self.rs.debug.hitboxes,
+            glfw::KeyV => self.rs.debug.velocities = !self.rs.debug.velocities,
+            _ => ()
+        }
+        Stay
+    }
+
+    fn action_pressed(&mut self, action: Action) -> Transition {
+        match action {
+            QuitAction => Reset(@mut TitleState::new(self.rs, self.settings) as @mut GameState),
+            _ => Stay
+        }
+    }
+
+    fn render(&self) {
+        self.rs.render(&self.view.game.world);
+    }
+}
@@ -1,27 +1,95 @@
 // SYSTEM DEFINITIONS
 extern mod std;
-use components::Components;
+use components::*;
+use collision::*;
+use resources::*;
+use entities::{LEFT,RIGHT};
 
 pub trait System {
-    fn process(&self, entity: @Components) -> ();
+    fn process(&self, entity: &mut Components, resources: &mut Resources) -> ();
+
+    // systems that need to look at several entities at once, eg. collision
+    // detection, can override this instead
+    fn process_all(&self, entities: &mut [Components], resources: &mut Resources) -> () {
+        for entity in entities.mut_iter() {
+            self.process(entity, resources);
+        }
+    }
 }
 
 pub struct MovementSystem;
 
 impl System for MovementSystem {
-    fn process(&self, entity: @Components) -> () {
-        match entity.position {
-            Some(pos) => {
-                match entity.vert_velocity {
-                    Some(v) => pos.y += v.y,
-                    None => ()
+    fn process(&self, entity: &mut Components, resources: &mut Resources) -> () {
+        let physics = &resources.physics;
+        match *entity {
+            Components { position: Some(ref mut pos), velocity: Some(ref mut vel),
+                         acceleration: ref acceleration, damping: ref damping, .. } => {
+                // only entities with Acceleration are affected by gravity
+                let (ax, ay) = match *acceleration {
+                    Some(ref acc) => {
+                        let (gx, gy) = physics.gravity;
+                        (acc.x + gx, acc.y + gy)
+                    },
+                    None => (0.0, 0.0)
+                };
+                match physics.integrator {
+                    ExplicitEuler => {
+                        pos.x += vel.x;
+                        pos.y += vel.y;
+                        vel.x += ax;
+                        vel.y += ay;
+                    },
+                    SemiImplicitEuler => {
+                        vel.x += ax;
+                        vel.y += ay;
+                        pos.x += vel.x;
+                        pos.y += vel.y;
+                    },
+                    // velocity Verlet, acceleration is constant over the tick
+                    Verlet => {
+                        pos.x += vel.x + 0.5*ax;
+                        pos.y += vel.y + 0.5*ay;
+                        vel.x += ax;
+                        vel.y += ay;
+                    }
                 }
-                match entity.horiz_velocity {
-                    Some(v) => pos.x += v.x,
+                match *damping {
+                    Some(ref damping) => {
+                        vel.x *= 1.0 - damping.factor;
+                        vel.y *= 1.0 - damping.factor;
+                    },
                     None => ()
                 }
             },
-            None => ()
+            _ => ()
+        }
+    }
+}
+
+// limits vertical velocity so that entities with MovementBounds stay inside them,
+// needs to run before MovementSystem
+pub struct BoundsSystem;
+
+impl System for BoundsSystem {
+    fn process(&self, entity: &mut Components, _: &mut Resources) -> () {
+        match *entity {
+            Components { position: Some(ref mut pos), velocity: Some(ref mut vel), bounds: Some(ref bounds), .. } => {
+                // put back entities that start outside their bounds
+                pos.y = pos.y.max(&bounds.min_y).min(&bounds.max_y);
+                let remaining = if vel.y > 0.0 {
+                    bounds.max_y - pos.y
+                } else {
+                    pos.y - bounds.min_y
+                };
+                let mut speed = std::num::abs(vel.y);
+                if bounds.ease_distance > 0.0 && remaining < bounds.ease_distance {
+                    speed *= remaining/bounds.ease_distance;
+                }
+                speed = speed.min(&remaining);
+                vel.y = if vel.y > 0.0 { speed } else { -speed };
+            },
+            _ => ()
         }
     }
 }
@@ -29,81 +97,199 @@ impl System for MovementSystem {
 pub struct EdgeCollisionSystem;
 
 impl System for EdgeCollisionSystem {
-    fn process(&self, entity: @Components) -> () {
-        match (entity.position, entity.vert_velocity, entity.sprite) {
-            (Some(pos), Some(vel), Some(spr)) => {
-                if (pos.y + (spr.y_size/2.0)) >= 3.0 {
-                    vel.y *= -1.0;
-                    pos.y = 3.0 - (spr.y_size/2.0);
+    // entities that were already overlapping an edge at the start of the tick
+    // don't produce collision events, so keep them inside the arena here. Only
+    // a velocity into the edge is turned around, one that process_all has
+    // just bounced may still touch the edge by rounding.
+    fn process(&self, entity: &mut Components, _: &mut Resources) -> () {
+        match *entity {
+            Components { position: Some(ref mut pos), velocity: Some(ref mut vel), collider: Some(ref col), .. }
+                    if col.mask & LAYER_WALL != 0 => {
+                let (_, half_height) = col.half_extents();
+                if (pos.y + half_height) >= 3.0 {
+                    vel.y = -std::num::abs(vel.y);
+                    pos.y = 3.0 - half_height;
                 }
-                if (pos.y - (spr.y_size/2.0)) <= 0.0 {
-                    vel.y *= -1.0;
-                    pos.y = spr.y_size/2.0;
+                if (pos.y - half_height) <= 0.0 {
+                    vel.y = std::num::abs(vel.y);
+                    pos.y = half_height;
                 }
             },
-            (_, _, _) => ()
+            _ => ()
         }
     }
-}
 
-pub struct ScoreCollisionSystem {
-    left_chan: Chan<uint>,
-    right_chan: Chan<uint>
+    fn process_all(&self, entities: &mut [Components], resources: &mut Resources) -> () {
+        for event in resources.collisions.events.iter() {
+            if !in_layer(&entities[event.b], LAYER_WALL) {
+                continue;
+            }
+            let entity = &mut entities[event.a];
+            if entity.velocity.is_some() {
+                let (_, ny) = event.normal;
+                rewind(entity, event.time);
+                let vel = entity.velocity.get_mut_ref();
+                vel.y = std::num::abs(vel.y)*ny;
+            }
+        }
+        for entity in entities.mut_iter() {
+            self.process(entity, resources);
+        }
+    }
 }
 
+pub struct ScoreCollisionSystem;
+
 impl System for ScoreCollisionSystem {
-    fn process(&self, entity: @Components) -> () {
-        match (entity.position, entity.vert_velocity, entity.horiz_velocity) {
-            (Some(pos), Some(vvel), Some(hvel)) => {
-                if pos.x > 4.0 {
-                    self.left_chan.send(1);
+    fn process(&self, entity: &mut Components, resources: &mut Resources) -> () {
+        if !in_layer(entity, LAYER_BALL) {
+            return
+        }
+        match *entity {
+            Components { position: Some(ref mut pos), velocity: Some(ref mut vel), .. } => {
+                let scorer = if pos.x > 4.0 {
+                    LEFT
                 } else if pos.x < 0.0 {
-                    self.right_chan.send(1);
+                    RIGHT
                 } else {
                     return
+                };
+                resources.match_state.point_scored(scorer);
+                if resources.match_state.is_over() {
+                    resources.serve.state = OutOfPlay;
+                } else {
+                    resources.serve.point_scored(scorer);
                 }
+                // ServeSystem takes the ball from here
                 pos.x = 2.0;
                 pos.y = 1.5;
-                hvel.x *= -1.0;
-                vvel.y = 0.0;
+                vel.x = 0.0;
+                vel.y = 0.0;
             },
-            (_, _, _) => ()
+            _ => ()
         }
     }
 }
 
-//AABB collision detection
-pub fn doEntitiesCollide(a: @Components, b: @Components) -> bool {
-    if std::managed::ptr_eq(a,b) {
-        false
-    } else {
-        match (a.position, a.sprite, b.position, b.sprite) {
-            (Some(a_pos), Some(a_spr), Some(b_pos), Some(b_spr)) => {
-                (std::num::abs(a_pos.x - b_pos.x) * 2.0 <= (a_spr.x_size + b_spr.x_size))
-                    && (std::num::abs(a_pos.y - b_pos.y) * 2.0 <= (a_spr.y_size + b_spr.y_size))
-            },
-            (_, _, _, _) => false
+pub struct PaddleCollisionSystem;
+
+impl System for PaddleCollisionSystem {
+    fn process(&self, _: &mut Components, _: &mut Resources) -> () {
+    }
+
+    fn process_all(&self, entities: &mut [Components], resources: &mut Resources) -> () {
+        let physics = &resources.ball_physics;
+        for event in resources.collisions.events.iter() {
+            if !in_layer(&entities[event.b], LAYER_PADDLE) {
+                continue;
+            }
+            let (_, paddle_vel) = velocity(&entities[event.b]);
+            let (paddle_pos, paddle_col) = (entities[event.b].position, entities[event.b].collider);
+            match (paddle_pos, paddle_col) {
+                (Some(paddle_pos), Some(paddle_col)) => {
+                    let ball = &mut entities[event.a];
+                    if ball.position.is_none() || ball.velocity.is_none() {
+                        continue;
+                    }
+                    // resolve to the point of impact
+                    rewind(ball, event.time);
+                    let pos = ball.position.unwrap();
+                    let vel = ball.velocity.get_mut_ref();
+                    let (nx, ny) = event.normal;
+                    if nx != 0.0 {
+                        let paddle_y = paddle_pos.y - paddle_vel*(1.0 - event.time);
+                        let paddle_distance = pos.y - paddle_y;
+                        let (_, paddle_height) = paddle_col.half_extents();
+                        let (vx, vy) = physics.paddle_bounce((vel.x, vel.y), nx,
+                                                             paddle_distance/paddle_height, paddle_vel);
+                        vel.x = vx;
+                        vel.y = vy;
+                    } else {
+                        // glancing hit on the top or bottom of the paddle
+                        vel.y = std::num::abs(vel.y - paddle_vel)*ny + paddle_vel;
+                    }
+                },
+                (_, _) => continue
+            }
+            squash(&mut entities[event.a], (0.6, 1.4));
+            squash(&mut entities[event.b], (1.0, 0.85));
         }
     }
 }
 
-pub struct PaddleCollisionSystem {
-    paddle: @Components,
+// squash-and-stretch: momentarily scale the sprite as drawn and spring back
+pub fn squash(entity: &mut Components, scale: (f64, f64)) {
+    match *entity {
+        Components { sprite: Some(_), tweens: Some(ref mut tweens), .. } => {
+            tweens.start(Tween::scale(scale, (1.0, 1.0), 12, BackOut));
+        },
+        _ => ()
+    }
 }
 
-impl System for PaddleCollisionSystem {
-    fn process(&self, entity: @Components) -> () {
-        if doEntitiesCollide(self.paddle, entity) {
-            match (entity.horiz_velocity, entity.vert_velocity, entity.position) {
-                (Some(hvel), Some(vvel), Some(pos)) => {
-                    let paddle_distance = pos.y - self.paddle.position.unwrap().y;
-                    let paddle_height = self.paddle.sprite.unwrap().y_size/2.0;
-                    hvel.x *= -1.0;
-                    vvel.y = 0.5*hvel.x*std::num::sinh(3.14*paddle_distance/paddle_height);
-                },
-                (_, _, _) => ()
-            }
+pub struct AnimationSystem;
+
+impl System for AnimationSystem {
+    fn process(&self, entity: &mut Components, _: &mut Resources) -> () {
+        match *entity {
+            Components { animation: Some(ref mut anim), sprite: Some(ref mut spr), .. } => {
+                if anim.frames.is_empty() || anim.finished() {
+                    return
+                }
+                anim.elapsed += 1;
+                if anim.elapsed >= anim.frame_ticks {
+                    if anim.current + 1 < anim.frames.len() {
+                        anim.elapsed = 0;
+                        anim.current += 1;
+                    } else if anim.looping {
+                        anim.elapsed = 0;
+                        anim.current = 0;
+                    }
+                }
+                spr.texture = anim.frame();
+            },
+            _ => ()
         }
     }
 }
 
+pub struct TweenSystem;
+
+impl System for TweenSystem {
+    fn process(&self, entity: &mut Components, _: &mut Resources) -> () {
+        match *entity {
+            Components { tweens: Some(ref mut tweens), position: ref mut position, sprite: ref mut sprite, .. } => {
+                for tween in tweens.active.mut_iter() {
+                    tween.elapsed += 1;
+                    let v = tween.value();
+                    match tween.property {
+                        TweenPosition => match *position {
+                            Some(ref mut pos) => {
+                                pos.x = v[0];
+                                pos.y = v[1];
+                            },
+                            None => ()
+                        },
+                        TweenSize => match *sprite {
+                            Some(ref mut spr) => {
+                                spr.x_size = v[0];
+                                spr.y_size = v[1];
+                            },
+                            None => ()
+                        },
+                        TweenColor => match *sprite {
+                            Some(ref mut spr) => spr.color = v,
+                            None => ()
+                        },
+                        TweenScale => match *sprite {
+                            Some(ref mut spr) => spr.scale = (v[0], v[1]),
+                            None => ()
+                        }
+                    }
+                }
+                tweens.active.retain(|t| !t.finished());
+            },
+            _ => ()
+        }
+    }
+}
@@ -0,0 +1,479 @@
+// TOURNAMENTS
+// bots play each other without a window, `pong tournament --help` lists the
+// options
+extern mod std;
+use std::io::fs::File;
+use std::io::io_error;
+use std::path::Path;
+use ai::*;
+use entities::{PaddleSide,LEFT,RIGHT};
+use game::{new_match_world,seeded_rng};
+use globalsystems::{GlobalSystem,BotInputSystem};
+use input::InputSnapshot;
+use resources::{MatchRules,ScorerServes,Countdown,ExplicitEuler};
+
+static SERVE_COUNTDOWN: uint = 30;
+static INITIAL_ELO: f64 = 1500.0;
+static ELO_K: f64 = 32.0;
+
+pub struct Contestant {
+    name: ~str,
+    ai: BotAi,
+    difficulty: BotDifficulty
+}
+
+// the original bot and every built-in strategy at every other level
+pub fn default_roster() -> ~[Contestant] {
+    let mut roster = ~[Contestant { name: ~"CLASSIC", ai: ChaserAi, difficulty: Classic.difficulty() }];
+    for ai in BOT_AIS.iter() {
+        for level in BOT_LEVELS.iter().filter(|&l| *l != Classic) {
+            roster.push(Contestant {
+                name: format!("{}-{}", ai.label(), level.label()),
+                ai: *ai,
+                difficulty: level.difficulty()
+            });
+        }
+    }
+    roster
+}
+
+// `NAME,AI,REACTION,SPEED,DEADZONE`, eg. `slow,chaser,10,1.0,0.1`, with the
+// reaction in ticks and the speed in arena units per second. These bots
+// never aim off or miss on purpose.
+pub fn parse_contestant(spec: &str) -> Option<Contestant> {
+    let parts: ~[&str] = spec.split(',').map(|p| p.trim()).collect();
+    if parts.len() != 5 || parts[0].is_empty() || parts[0].contains_char('"') {
+        return None;
+    }
+    match (BotAi::from_name(parts[1]), from_str::<uint>(parts[2]), from_str::<f64>(parts[3]), from_str::<f64>(parts[4])) {
+        (Some(ai), Some(reaction), Some(speed), Some(deadzone)) if speed > 0.0 && deadzone >= 0.0 => Some(Contestant {
+            name: parts[0].to_owned(),
+            ai: ai,
+            difficulty: BotDifficulty {
+                reaction_ticks: reaction,
+                prediction_error: 0.0,
+                max_speed: speed/60.0,
+                miss_chance: 0.0,
+                deadzone: deadzone
+            }
+        }),
+        (_, _, _, _) => None
+    }
+}
+
+// one contestant per line as in parse_contestant, lines starting with # are
+// comments
+pub fn load_roster(path: &str) -> Option<~[Contestant]> {
+    let bytes = io_error::cond.trap(|_| ()).inside(|| {
+        match File::open(&Path::new(path)) {
+            Some(mut f) => Some(f.read_to_end()),
+            None => None
+        }
+    });
+    let src = match bytes {
+        Some(bytes) if std::str::is_utf8(bytes) => std::str::from_utf8_owned(bytes),
+        _ => {
+            println!("tournament: can't read {:s}", path);
+            return None;
+        }
+    };
+    let mut roster = ~[];
+    for line in src.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        match parse_contestant(line) {
+            Some(contestant) => roster.push(contestant),
+            None => {
+                println!("tournament: bad contestant `{:s}` in {:s}", line, path);
+                return None;
+            }
+        }
+    }
+    Some(roster)
+}
+
+pub enum TournamentFormat {
+    RoundRobin,
+    // contestants with similar scores meet, for this many rounds
+    Swiss(uint)
+}
+
+pub struct TournamentConfig {
+    format: TournamentFormat,
+    // matches per pairing, sides alternate between them
+    matches: uint,
+    rules: MatchRules,
+    // match i is played with seed + i
+    seed: u32,
+    // matches still going after this many ticks are draws
+    max_ticks: uint
+}
+
+impl TournamentConfig {
+    pub fn new() -> TournamentConfig {
+        TournamentConfig {
+            format: RoundRobin,
+            matches: 2,
+            rules: MatchRules::new(),
+            seed: 1,
+            max_ticks: 60*60*30
+        }
+    }
+}
+
+pub struct MatchRecord {
+    round: uint,
+    seed: u32,
+    // indices into the roster
+    left: uint,
+    right: uint,
+    // total points, games and sets, left first
+    points: [uint, ..2],
+    games: [uint, ..2],
+    sets: [uint, ..2],
+    ticks: uint,
+    winner: Option<uint>
+}
+
+pub struct Standing {
+    played: uint,
+    wins: uint,
+    draws: uint,
+    losses: uint,
+    points_for: uint,
+    points_against: uint,
+    elo: f64,
+    // contestants already met, for Swiss pairings
+    opponents: ~[uint]
+}
+
+impl Standing {
+    fn new() -> Standing {
+        Standing { played: 0, wins: 0, draws: 0, losses: 0, points_for: 0, points_against: 0, elo: INITIAL_ELO, opponents: ~[] }
+    }
+
+    // a win is worth 1 and a draw 0.5
+    pub fn score(&self) -> f64 {
+        self.wins as f64 + 0.5*self.draws as f64
+    }
+}
+
+pub struct Tournament {
+    roster: ~[Contestant],
+    config: TournamentConfig,
+    standings: ~[Standing],
+    matches: ~[MatchRecord]
+}
+
+// how a single match ended, per-side arrays are indexed with PaddleSide::index
+struct MatchOutcome {
+    points: [uint, ..2],
+    games: [uint, ..2],
+    sets: [uint, ..2],
+    ticks: uint,
+    winner: Option<PaddleSide>
+}
+
+fn play_match(left: &Contestant, right: &Contestant, rules: MatchRules, seed: u32, max_ticks: uint) -> MatchOutcome {
+    let mut game = new_match_world(rules, ScorerServes, ExplicitEuler, seeded_rng(seed));
+    game.world.resources.serve.countdown_ticks = SERVE_COUNTDOWN;
+    game.world.resources.serve.state = Countdown(SERVE_COUNTDOWN);
+    for &(contestant, side) in [(left, LEFT), (right, RIGHT)].iter() {
+        let (paddle, opponent) = (game.paddle(side), game.paddle(side.opponent()));
+        game.world.resources.bot_difficulty[side.index()] = contestant.difficulty;
+        let bis = ~BotInputSystem::new(side, paddle, game.ball, contestant.ai.strategy(paddle, opponent, false));
+        game.world.global_systems.push(bis as ~GlobalSystem);
+    }
+
+    let input = InputSnapshot::none();
+    let mut ticks = 0;
+    while !game.world.resources.match_state.is_over() && ticks < max_ticks {
+        game.world.process(&input);
+        ticks += 1;
+    }
+    let m = &game.world.resources.match_state;
+    MatchOutcome { points: m.total_points, games: m.games, sets: m.sets, ticks: ticks, winner: m.winner }
+}
+
+fn expected_score(elo: f64, opponent_elo: f64) -> f64 {
+    1.0/(1.0 + (10.0f64).powf(&((opponent_elo - elo)/400.0)))
+}
+
+impl Tournament {
+    pub fn new(roster: ~[Contestant], config: TournamentConfig) -> Tournament {
+        let standings = roster.iter().map(|_| Standing::new()).collect();
+        Tournament { roster: roster, config: config, standings: standings, matches: ~[] }
+    }
+
+    pub fn run(&mut self) {
+        match self.config.format {
+            RoundRobin => {
+                let n = self.roster.len();
+                for a in range(0, n) {
+                    for b in range(a + 1, n) {
+                        self.play_pairing(0, a, b);
+                    }
+                }
+            },
+            Swiss(rounds) => {
+                for round in range(0, rounds) {
+                    for &(a, b) in self.swiss_pairings().iter() {
+                        self.play_pairing(round + 1, a, b);
+                    }
+                }
+            }
+        }
+    }
+
+    // pairs contestants with similar scores who haven't met yet, the one left
+    // over with an odd number of contestants sits the round out
+    fn swiss_pairings(&self) -> ~[(uint, uint)] {
+        let mut paired = ~[];
+        let mut waiting = self.ranking();
+        while waiting.len() >= 2 {
+            let a = waiting.shift();
+            let partner = match waiting.iter().position(|b| !self.standings[a].opponents.contains(b)) {
+                Some(i) => i,
+                None => 0
+            };
+            let b = waiting.remove(partner);
+            paired.push((a, b));
+        }
+        paired
+    }
+
+    fn play_pairing(&mut self, round: uint, a: uint, b: uint) {
+        for i in range(0, self.config.matches) {
+            let (left, right) = if i % 2 == 0 { (a, b) } else { (b, a) };
+            let seed = self.config.seed + self.matches.len() as u32;
+            let outcome = play_match(&self.roster[left], &self.roster[right],
+                                     self.config.rules, seed, self.config.max_ticks);
+            let record = MatchRecord {
+                round: round,
+                seed: seed,
+                left: left,
+                right: right,
+                points: outcome.points,
+                games: outcome.games,
+                sets: outcome.sets,
+                ticks: outcome.ticks,
+                winner: outcome.winner.map(|side| if side == LEFT { left } else { right })
+            };
+            self.record(&record);
+            println!("{:s} vs {:s}: {:u}-{:u}", self.roster[left].name, self.roster[right].name,
+                     record.points[0], record.points[1]);
+            self.matches.push(record);
+        }
+    }
+
+    fn record(&mut self, record: &MatchRecord) {
+        let (l, r) = (record.left, record.right);
+        // 1.0 for a left win, 0.0 for a right win
+        let result = match record.winner {
+            Some(w) if w == l => 1.0,
+            Some(_) => 0.0,
+            None => 0.5
+        };
+        let (elo_l, elo_r) = (self.standings[l].elo, self.standings[r].elo);
+        let change = ELO_K*(result - expected_score(elo_l, elo_r));
+        for &(i, side, opponent, delta) in [(l, 0u, r, change), (r, 1u, l, -change)].iter() {
+            let s = &mut self.standings[i];
+            s.played += 1;
+            s.points_for += record.points[side];
+            s.points_against += record.points[1 - side];
+            s.elo += delta;
+            if !s.opponents.contains(&opponent) {
+                s.opponents.push(opponent);
+            }
+            match record.winner {
+                Some(w) if w == i => s.wins += 1,
+                Some(_) => s.losses += 1,
+                None => s.draws += 1
+            }
+        }
+    }
+
+    // roster indices from first to last place
+    pub fn ranking(&self) -> ~[uint] {
+        let mut order: ~[uint] = range(0, self.roster.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (sa, sb) = (&self.standings[a], &self.standings[b]);
+            let key_a = (sa.score(), sa.elo);
+            let key_b = (sb.score(), sb.elo);
+            if key_a > key_b { Less } else if key_a < key_b { Greater } else { Equal }
+        });
+        order
+    }
+
+    fn winner_name(&self, record: &MatchRecord) -> ~str {
+        match record.winner {
+            Some(w) => self.roster[w].name.clone(),
+            None => ~""
+        }
+    }
+
+    pub fn standings_csv(&self) -> ~str {
+        let mut s = ~"rank,name,played,wins,draws,losses,points_for,points_against,elo\n";
+        for (rank, &i) in self.ranking().iter().enumerate() {
+            let st = &self.standings[i];
+            s.push_str(format!("{},{},{},{},{},{},{},{},{:.1f}\n", rank + 1, self.roster[i].name,
+                               st.played, st.wins, st.draws, st.losses, st.points_for, st.points_against, st.elo));
+        }
+        s
+    }
+
+    pub fn matches_csv(&self) -> ~str {
+        let mut s = ~"round,seed,left,right,left_points,right_points,left_games,right_games,left_sets,right_sets,ticks,winner\n";
+        for m in self.matches.iter() {
+            s.push_str(format!("{},{},{},{},{},{},{},{},{},{},{},{}\n", m.round, m.seed,
+                               self.roster[m.left].name, self.roster[m.right].name,
+                               m.points[0], m.points[1], m.games[0], m.games[1], m.sets[0], m.sets[1],
+                               m.ticks, self.winner_name(m)));
+        }
+        s
+    }
+
+    pub fn to_json(&self) -> ~str {
+        let standings: ~[~str] = self.ranking().iter().enumerate().map(|(rank, &i)| {
+            let st = &self.standings[i];
+            format!("\\{\"rank\":{},\"name\":\"{}\",\"played\":{},\"wins\":{},\"draws\":{},\"losses\":{},\
+                     \"points_for\":{},\"points_against\":{},\"elo\":{:.1f}\\}",
+                    rank + 1, self.roster[i].name, st.played, st.wins, st.draws, st.losses,
+                    st.points_for, st.points_against, st.elo)
+        }).collect();
+        let matches: ~[~str] = self.matches.iter().map(|m| {
+            format!("\\{\"round\":{},\"seed\":{},\"left\":\"{}\",\"right\":\"{}\",\"points\":[{},{}],\
+                     \"games\":[{},{}],\"sets\":[{},{}],\"ticks\":{},\"winner\":\"{}\"\\}",
+                    m.round, m.seed, self.roster[m.left].name, self.roster[m.right].name,
+                    m.points[0], m.points[1], m.games[0], m.games[1], m.sets[0], m.sets[1],
+                    m.ticks, self.winner_name(m))
+        }).collect();
+        format!("\\{\"standings\":[{}],\"matches\":[{}]\\}\n", standings.connect(","), matches.connect(","))
+    }
+}
+
+fn write_file(path: &str, contents: &str) {
+    let mut failed = false;
+    io_error::cond.trap(|_| failed = true).inside(|| {
+        match File::create(&Path::new(path)) {
+            Some(mut f) => f.write(contents.as_bytes()),
+            None => ()
+        }
+    });
+    if failed {
+        println!("tournament: can't write {:s}", path);
+    }
+}
+
+static USAGE: &'static str = "usage: pong tournament [options]
+  --swiss ROUNDS     play a Swiss tournament instead of a round robin
+  --matches N        matches per pairing, sides alternate (2)
+  --points N         points to win a game (11)
+  --seed N           seed of the first match (1)
+  --max-ticks N      matches are drawn after this many ticks (108000)
+  --out PREFIX       writes PREFIX_standings.csv, PREFIX_matches.csv and
+                     PREFIX.json (tournament)
+  --bot SPEC         adds a contestant, NAME,AI,REACTION,SPEED,DEADZONE with
+                     AI chaser, predictor or aimer, REACTION in ticks and
+                     SPEED in arena units per second, eg. slow,chaser,10,1,0.1
+  --roster FILE      adds the contestants in FILE, one SPEC per line
+The built-in bots play unless --bot or --roster is given.";
+
+// `args` are the ones following `tournament` on the command line
+pub fn run(args: &[~str]) {
+    let mut config = TournamentConfig::new();
+    let mut out = ~"tournament";
+    let mut roster = ~[];
+    let mut i = 0;
+    while i < args.len() {
+        let value = if i + 1 < args.len() { from_str::<uint>(args[i + 1]) } else { None };
+        match (args[i].as_slice(), value) {
+            ("--swiss", Some(rounds)) => config.format = Swiss(rounds),
+            ("--matches", Some(n)) => config.matches = n,
+            ("--points", Some(n)) => config.rules.points_to_win = n,
+            ("--seed", Some(n)) => config.seed = n as u32,
+            ("--max-ticks", Some(n)) => config.max_ticks = n,
+            ("--out", _) if i + 1 < args.len() => out = args[i + 1].clone(),
+            ("--bot", _) if i + 1 < args.len() => match parse_contestant(args[i + 1]) {
+                Some(contestant) => roster.push(contestant),
+                None => {
+                    println!("tournament: bad contestant `{:s}`\n{:s}", args[i + 1], USAGE);
+                    return;
+                }
+            },
+            ("--roster", _) if i + 1 < args.len() => match load_roster(args[i + 1]) {
+                Some(contestants) => roster.push_all_move(contestants),
+                None => return
+            },
+            (_, _) => {
+                println!("{:s}", USAGE);
+                return;
+            }
+        }
+        i += 2;
+    }
+
+    if roster.is_empty() {
+        roster = default_roster();
+    } else if roster.len() < 2 {
+        println!("tournament: needs at least two contestants");
+        return;
+    }
+    let mut tournament = Tournament::new(roster, config);
+    tournament.run();
+    write_file(format!("{:s}_standings.csv", out), tournament.standings_csv());
+    write_file(format!("{:s}_matches.csv", out), tournament.matches_csv());
+    write_file(format!("{:s}.json", out), tournament.to_json());
+    print!("{:s}", tournament.standings_csv());
+}
+
+#[cfg(test)]
+mod test {
+    use tournament::*;
+    use super::expected_score;
+
+    fn two_contestants() -> Tournament {
+        let roster = ~[parse_contestant("a,chaser,0,1.0,0.1").unwrap(),
+                       parse_contestant("b,chaser,0,1.0,0.1").unwrap()];
+        Tournament::new(roster, TournamentConfig::new())
+    }
+
+    fn record(winner: Option<uint>) -> MatchRecord {
+        MatchRecord { round: 0, seed: 1, left: 0, right: 1, points: [11, 4], games: [2, 0], sets: [1, 0], ticks: 100, winner: winner }
+    }
+
+    #[test]
+    fn expected_scores() {
+        assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+        assert!((expected_score(1900.0, 1500.0) - 10.0/11.0).abs() < 1e-9);
+        assert!((expected_score(1600.0, 1450.0) + expected_score(1450.0, 1600.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn record_a_win() {
+        let mut t = two_contestants();
+        t.record(&record(Some(0)));
+        let (a, b) = (&t.standings[0], &t.standings[1]);
+        assert_eq!((a.played, a.wins, a.losses, a.draws), (1, 1, 0, 0));
+        assert_eq!((b.played, b.wins, b.losses, b.draws), (1, 0, 1, 0));
+        assert_eq!((a.points_for, a.points_against), (11, 4));
+        assert_eq!((b.points_for, b.points_against), (4, 11));
+        assert_eq!(a.elo, 1516.0);
+        assert_eq!(b.elo, 1484.0);
+        assert_eq!(a.opponents, ~[1]);
+        assert_eq!(b.opponents, ~[0]);
+    }
+
+    #[test]
+    fn record_a_draw_between_equals() {
+        let mut t = two_contestants();
+        t.record(&record(None));
+        assert_eq!(t.standings[0].elo, 1500.0);
+        assert_eq!(t.standings[1].draws, 1);
+        // meeting again doesn't list the opponent twice
+        t.record(&record(None));
+        assert_eq!(t.standings[0].opponents, ~[1]);
+    }
+}
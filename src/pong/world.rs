@@ -1,30 +1,39 @@
 // WORLD DEFINITION
-extern mod glfw;
 use components::Components;
 use systems::System;
 use globalsystems::GlobalSystem;
+use input::InputSnapshot;
+use resources::Resources;
 
 // We need to figure out how to integrate World with the main game loop
 // in Artemis world has a `setDelta` method for timestep
+// A world owns everything in it, so that it can be sent to another task, eg.
+// a worker of batch::BatchEnv. Drawing is up to whoever shows it.
 pub struct World {
-    entities: ~[@Components],
-    systems: ~[@System],
-    global_systems: ~[@mut GlobalSystem]
+    // entity ids are indices into this, so entities are never removed
+    entities: ~[Components],
+    resources: Resources,
+    systems: ~[~System],
+    global_systems: ~[~GlobalSystem]
 }
 
 impl World {
     pub fn new() -> World {
-        return World {entities: ~[], systems: ~[], global_systems: ~[]};
+        return World {entities: ~[], resources: Resources::new(), systems: ~[], global_systems: ~[]};
     }
 
-    pub fn process(&self, window: &glfw::Window) {
-        for system in self.global_systems.iter() {
-            system.process(window);
+    // adds an entity and returns its id
+    pub fn add(&mut self, entity: Components) -> uint {
+        self.entities.push(entity);
+        self.entities.len() - 1
+    }
+
+    pub fn process(&mut self, input: &InputSnapshot) {
+        for system in self.global_systems.mut_iter() {
+            system.process(self.entities, &mut self.resources, input);
         }
         for system in self.systems.iter() {
-            for entity in self.entities.iter() {
-                system.process(*entity);
-            }
+            system.process_all(self.entities, &mut self.resources);
         }
     }
 }